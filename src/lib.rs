@@ -1,10 +1,16 @@
 use std::{
     collections::HashMap,
-    env::var,
+    env::{self, VarError},
     error::Error,
-    path::{Path, PathBuf},
-    process::Command,
-    str::FromStr,
+    fmt::{self, Display, Formatter},
+    fs,
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    process::{self, Command, ExitStatus, Output, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
 };
 
 /// Builder for a C3 FFI. Compiles the given files into a static/dynamic library which can then be used from within Rust.
@@ -12,37 +18,200 @@ use std::{
 /// For alternative name, see: [Build].
 pub struct C3FFI {
     compiler: String,
+    track_compiler: bool,
     linking_mode: LinkingMode,
     optimization_level: OptimizationLevel,
+    optimization_level_overrides: Vec<(String, OptimizationLevel)>,
     debug_info: bool,
+    verbose: bool,
+    compiler_verbosity: Option<u8>,
+    emit_llvm: bool,
+    emit_asm: bool,
+    out_dir: Option<PathBuf>,
+    intermediates_dir: Option<PathBuf>,
+    single_module: Option<bool>,
     files: Vec<PathBuf>,
     features: Vec<String>,
+    conflicting_features: Vec<(String, String)>,
     args: Vec<String>,
     environment_variables: Vec<(String, String)>,
+    phase_environment_variables: Vec<(Phase, String, String)>,
+    case_insensitive_env: Option<bool>,
     linker_arguments: Vec<String>,
     compiled_lib_dirs: Vec<PathBuf>,
     compiled_libs: Vec<PathBuf>,
+    check_libs_exist: bool,
     c3_lib_dirs: Vec<PathBuf>,
     c3_libs: Vec<PathBuf>,
+    create_output_dir: bool,
+    content_addressed: bool,
+    cache: bool,
+    extension_overrides: Vec<(String, LinkingMode, String)>,
+    stack_size: Option<u64>,
+    merged_libs: Vec<PathBuf>,
+    required_feature: Option<String>,
+    reproducible: bool,
+    codegen_seed: Option<u64>,
+    kept_symbols: Vec<String>,
+    link_dead_code: bool,
+    on_error: Option<ErrorHandler>,
+    rust_bindings: Option<PathBuf>,
+    forward_warnings: bool,
+    max_forwarded_warnings: Option<usize>,
+    check_only: bool,
+    subcommand: Option<String>,
+    subcommand_overrides: Vec<(LinkingMode, String)>,
+    check_subcommand: Option<String>,
+    reloc_model: Option<RelocModel>,
+    object_format: Option<ObjectFormat>,
+    sanitizer: Option<Sanitizer>,
+    libc: Option<LibcKind>,
+    panic_strategy: PanicStrategy,
+    panic_messages: Option<bool>,
+    backtrace: Option<bool>,
+    project: Option<PathBuf>,
+    build_config: Option<String>,
+    optional: bool,
+    success_cfgs: Vec<String>,
+    links_key: Option<String>,
+    architecture_overrides: Vec<(String, String)>,
+    c3_target: Option<String>,
+    verify_target: bool,
+    generated_sources: Vec<(String, Vec<u8>)>,
+    c3_extensions: Vec<String>,
+    required_stdlib_version: Option<String>,
+    required_min_version: Option<(u32, u32, u32)>,
+    split_output: Option<usize>,
+    link_sysroot: Option<PathBuf>,
+    macos_sdk: Option<PathBuf>,
+    windows_sdk: Option<PathBuf>,
+    windows_crt: Option<WinCrt>,
+    relative_link_search: bool,
+    diagnostic_classifier: Option<DiagnosticClassifier>,
+    error_format: ErrorFormat,
+    diagnostics_json: Option<PathBuf>,
+    args_modifier: Option<ArgsModifier>,
+    prebuild_commands: Vec<(String, Vec<String>)>,
+    build_manifest: Option<PathBuf>,
+    command_dump: Option<PathBuf>,
+    dumped_commands: Vec<String>,
+    mask_dumped_environment: bool,
+    verify_link: bool,
+    #[cfg(feature = "smoke-test")]
+    smoke_test_symbols: Vec<String>,
+    memory_limit: Option<u64>,
+    threads: Option<usize>,
+    inherit_cargo_jobs: bool,
+    max_parallel: Option<usize>,
+    generated_files: Vec<PathBuf>,
+    compiler_outputs: Vec<Output>,
+    link_directives: Vec<String>,
+    defer_link_directives: bool,
 }
 
+/// A callback set with [C3FFI::diagnostic_classifier].
+type DiagnosticClassifier = Box<dyn Fn(&str) -> DiagnosticSeverity>;
+
+/// A callback set with [C3FFI::modify_args].
+type ArgsModifier = Box<dyn FnMut(&mut Vec<String>)>;
+
+/// A callback set with [C3FFI::on_error].
+type ErrorHandler = Box<dyn FnOnce(&C3Error) -> String>;
+
 impl C3FFI {
     /// Initializes [C3FFI] with the default values.
     pub fn new() -> Self {
         Self {
             compiler: "c3c".to_string(),
+            track_compiler: false,
             linking_mode: LinkingMode::Static,
             optimization_level: OptimizationLevel::O0,
+            optimization_level_overrides: Vec::new(),
             debug_info: true,
+            verbose: false,
+            compiler_verbosity: None,
+            emit_llvm: false,
+            emit_asm: false,
+            out_dir: None,
+            intermediates_dir: None,
+            single_module: None,
             files: Vec::new(),
             features: Vec::new(),
+            conflicting_features: Vec::new(),
             args: Vec::new(),
             environment_variables: Vec::new(),
+            phase_environment_variables: Vec::new(),
+            case_insensitive_env: None,
             linker_arguments: Vec::new(),
             compiled_lib_dirs: Vec::new(),
             compiled_libs: Vec::new(),
+            check_libs_exist: false,
             c3_lib_dirs: Vec::new(),
             c3_libs: Vec::new(),
+            create_output_dir: true,
+            content_addressed: false,
+            cache: false,
+            extension_overrides: Vec::new(),
+            stack_size: None,
+            merged_libs: Vec::new(),
+            required_feature: None,
+            reproducible: false,
+            codegen_seed: None,
+            kept_symbols: Vec::new(),
+            link_dead_code: false,
+            on_error: None,
+            rust_bindings: None,
+            forward_warnings: false,
+            max_forwarded_warnings: None,
+            check_only: false,
+            subcommand: None,
+            subcommand_overrides: Vec::new(),
+            check_subcommand: None,
+            reloc_model: None,
+            object_format: None,
+            sanitizer: None,
+            libc: None,
+            panic_strategy: PanicStrategy::Default,
+            panic_messages: None,
+            backtrace: None,
+            project: None,
+            build_config: None,
+            optional: false,
+            success_cfgs: Vec::new(),
+            links_key: None,
+            architecture_overrides: Vec::new(),
+            c3_target: None,
+            verify_target: false,
+            generated_sources: Vec::new(),
+            c3_extensions: Vec::new(),
+            required_stdlib_version: None,
+            required_min_version: None,
+            split_output: None,
+            link_sysroot: None,
+            macos_sdk: None,
+            windows_sdk: None,
+            windows_crt: None,
+            relative_link_search: false,
+            diagnostic_classifier: None,
+            error_format: ErrorFormat::Plain,
+            diagnostics_json: None,
+            args_modifier: None,
+            prebuild_commands: Vec::new(),
+            build_manifest: None,
+            command_dump: None,
+            dumped_commands: Vec::new(),
+            mask_dumped_environment: false,
+            verify_link: false,
+            #[cfg(feature = "smoke-test")]
+            smoke_test_symbols: Vec::new(),
+            memory_limit: None,
+            threads: None,
+            inherit_cargo_jobs: false,
+            max_parallel: None,
+            generated_files: Vec::new(),
+            compiler_outputs: Vec::new(),
+            link_directives: Vec::new(),
+            defer_link_directives: false,
         }
     }
 
@@ -63,703 +232,7451 @@ impl C3FFI {
         self
     }
 
-    /// Whether the library is dynamically or statically linked.
+    /// Whether the build script should re-run when the c3c binary changes, so upgrading c3c in place rebuilds the
+    /// library.
     ///
-    /// Default: [LinkingMode::Static].
+    /// Default: false.
+    ///
+    /// When `true`, the compiler is resolved through `PATH` if it is not a path, `cargo::rerun-if-changed` is emitted
+    /// for it, and its modification time and version are part of the configuration hash used by
+    /// [C3FFI::content_addressed].
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .linking_mode(LinkingMode::Dynamic)
+    ///     .track_compiler(true)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn linking_mode(&mut self, linking_mode: LinkingMode) -> &mut Self {
-        self.linking_mode = linking_mode;
+    pub fn track_compiler(&mut self, track_compiler: bool) -> &mut Self {
+        self.track_compiler = track_compiler;
         self
     }
 
-    /// The library's optimization level.
+    /// Requires the standard library bundled with the compiler to be of the given version, failing the compilation
+    /// otherwise.
     ///
-    /// Default: [OptimizationLevel::O0].
+    /// The version is read from `c3c --version`, falling back to the compiler's version when c3c does not report the
+    /// standard library's separately. A partial version like `0.7` accepts any `0.7.x`.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .optimization_level(OptimizationLevel::O1)
+    ///     .require_stdlib_version("0.7.6")
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn optimization_level(&mut self, optimization_level: OptimizationLevel) -> &mut Self {
-        self.optimization_level = optimization_level;
+    pub fn require_stdlib_version(&mut self, version: &str) -> &mut Self {
+        self.required_stdlib_version = Some(version.to_string());
         self
     }
 
-    /// Whether debug information should be included or not.
-    ///
-    /// Default: true.
-    ///
-    /// When `false`, this is equivalent to calling c3c with `-g0`.
+    /// Requires c3c to be at least the given version, failing the compilation with [C3Error::CompilerTooOld] otherwise,
+    /// for code or flags that older releases do not understand.
     ///
-    /// When `true`, this is equivalent to calling c3c with `-g`.
+    /// The version is read from `c3c --version`. Missing components count as `0`, so `0.7` accepts `0.7.0` and later.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .debug_info(false)
+    ///     .require_min_version("0.7.1")
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn debug_info(&mut self, debug_info: bool) -> &mut Self {
-        self.debug_info = debug_info;
+    pub fn require_min_version(&mut self, version: &str) -> &mut Self {
+        match parse_version(version) {
+            Some(version) => self.required_min_version = Some(version),
+            None => println!("cargo::warning=ignoring invalid minimum c3c version {:?}", version),
+        }
         self
     }
 
-    /// Marks a file as target for compilation.
+    /// Whether the library is dynamically or statically linked.
     ///
-    /// Equivalent to calling c3c with the path to a source file.
+    /// Default: [LinkingMode::Static].
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
+    ///     .linking_mode(LinkingMode::Dynamic)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn file<P>(&mut self, file: P) -> &mut Self
-    where
-        P: Into<PathBuf>,
-    {
-        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
-        let file = file.into() as PathBuf;
-        if !self.files.contains(&file) {
-            println!("cargo::rerun-if-changed={}", file.display());
-            self.files.push(file);
-        }
-
+    pub fn linking_mode(&mut self, linking_mode: LinkingMode) -> &mut Self {
+        self.linking_mode = linking_mode;
         self
     }
 
-    /// Marks one or more files for compilation.
+    /// Merges the given prebuilt static libraries into the C3 library once it is built, so dependents only need to link a
+    /// single self-contained archive. Ignored, with a cargo warning, for [LinkingMode::Dynamic].
     ///
-    /// Equivalent to calling c3c with the path to several source files.
+    /// The archives are combined with `libtool` on Apple targets, `lib.exe` on MSVC targets and otherwise with the `ar`
+    /// from the `AR` environment variable, or `ar`, failing with [C3Error::ArchiveMergeFailed] if the tool does. The
+    /// merged archive replaces the library, so the link directives stay the same.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .files(["extern/thingmabob.c3", "extern/thingmajane.c3"])
+    ///     .merge_with(&["prebuilt/libhelpers.a".into()])
+    ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn files<P>(&mut self, files: P) -> &mut Self
-    where
-        P: IntoIterator,
-        P::Item: Into<PathBuf>,
-    {
-        for file in files {
-            self.file(file.into());
+    pub fn merge_with(&mut self, libs: &[PathBuf]) -> &mut Self {
+        for lib in libs {
+            if !self.merged_libs.contains(lib) {
+                println!("cargo::rerun-if-changed={}", lib.display());
+                self.merged_libs.push(lib.clone());
+            }
         }
-
         self
     }
 
-    /// Turns on a feature for the provided source files.
+    /// The library's optimization level.
     ///
-    /// Equivalent to calling c3c with `-D <feature>`.
+    /// Default: [OptimizationLevel::O0].
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .feature("foo")
+    ///     .optimization_level(OptimizationLevel::O1)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn feature(&mut self, feature: &str) -> &mut Self {
-        if !self.features.contains(&feature.to_string()) {
-            self.features.push(feature.to_string());
-        }
+    pub fn optimization_level(&mut self, optimization_level: OptimizationLevel) -> &mut Self {
+        self.optimization_level = optimization_level;
         self
     }
 
-    /// Turns on one or more features for the provided source files.
+    /// Overrides the optimization level when the c3c target's OS or architecture matches `os_or_arch`, e.g. `Os` for
+    /// `wasm32` while other targets use the level set with [C3FFI::optimization_level].
     ///
-    /// Equivalent to calling c3c with `-D <feature1> [-D <feature2> ... -D <featureN>]`.
+    /// Names follow c3c's target naming, such as `linux`, `macos`, `x64` or `aarch64`. When both an OS and an
+    /// architecture override match, the one registered first wins.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .features(["foo", "bar"])
+    ///     .optimization_level(OptimizationLevel::O3)
+    ///     .optimization_level_for("wasm32", OptimizationLevel::Os)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn features<P>(&mut self, features: P) -> &mut Self
-    where
-        P: IntoIterator,
-        P::Item: Into<String>,
-    {
-        for feature in features {
-            let feature = feature.into() as String;
-            self.feature(&feature);
+    pub fn optimization_level_for(&mut self, os_or_arch: &str, optimization_level: OptimizationLevel) -> &mut Self {
+        match self
+            .optimization_level_overrides
+            .iter_mut()
+            .find(|(existing_os_or_arch, _)| existing_os_or_arch == os_or_arch)
+        {
+            Some(existing) => existing.1 = optimization_level,
+            None => self
+                .optimization_level_overrides
+                .push((os_or_arch.to_string(), optimization_level)),
         }
 
         self
     }
 
-    /// Adds a custom argument to be passed to the compiler.
+    /// Whether all modules are compiled together as a single module, allowing inlining across modules.
+    ///
+    /// Default: unset, in which case [OptimizationLevel::O3] and above use a single module and lower levels do not.
+    ///
+    /// When `true`, this is equivalent to calling c3c with `--single-module=yes`, which forces it at any optimization
+    /// level. When `false`, this is equivalent to calling c3c with `--single-module=no`, which also turns it off for the
+    /// levels that would imply it. A single module yields one object file instead of one per module, which makes
+    /// [C3FFI::object_map] return nothing.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .arg("--no-headers")
+    ///     .optimization_level(OptimizationLevel::O1)
+    ///     .single_module(true)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn arg(&mut self, arg: &str) -> &mut Self {
-        if !self.args.contains(&arg.to_string()) {
-            self.args.push(arg.to_string());
-        }
+    pub fn single_module(&mut self, single_module: bool) -> &mut Self {
+        self.single_module = Some(single_module);
         self
     }
 
-    /// Adds one or more custom arguments to be passed to the compiler.
+    /// Whether debug information should be included or not.
+    ///
+    /// Default: true.
+    ///
+    /// When `false`, this is equivalent to calling c3c with `-g0`.
+    ///
+    /// When `true`, this is equivalent to calling c3c with `-g`.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .args(["--no-headers", "--use-old-enums"])
+    ///     .debug_info(false)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn args<P>(&mut self, args: P) -> &mut Self
-    where
-        P: IntoIterator,
-        P::Item: Into<String>,
-    {
-        for arg in args {
-            let arg = arg.into() as String;
-            self.arg(&arg);
-        }
-
+    pub fn debug_info(&mut self, debug_info: bool) -> &mut Self {
+        self.debug_info = debug_info;
         self
     }
 
-    /// Marks an argument to be passed to the linker.
+    /// Whether c3c should report what it is doing in detail.
     ///
-    /// Equivalent to calling c3c with `-z <arg>`.
+    /// Default: false.
+    ///
+    /// When `true`, this is equivalent to calling c3c with `-v`, and everything c3c prints is forwarded to the build
+    /// script's stderr, which cargo shows with `cargo build -vv`.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .linker_argument("--enable-linker-version")
+    ///     .verbose(true)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn linker_argument(&mut self, linker_argument: &str) -> &mut Self {
-        if !self.linker_arguments.contains(&linker_argument.to_string()) {
-            self.linker_arguments.push(linker_argument.to_string());
-        }
+    pub fn verbose(&mut self, verbose: bool) -> &mut Self {
+        self.verbose = verbose;
         self
     }
 
-    /// Marks one or more arguments to be passed to the linker.
+    /// How much detail c3c itself reports, from `0` for nothing extra to `3` for everything.
     ///
-    /// Equivalent to calling c3c with `-z <arg1> [-z <arg2> ... -z <argN>]`.
+    /// Default: unset, which means `1` when [C3FFI::verbose] is `true` and `0` otherwise.
+    ///
+    /// Equivalent to calling c3c with `-v`, `-vv` or `-vvv` for levels 1 to 3, higher levels being treated as 3. Unlike
+    /// [C3FFI::verbose], this does not forward c3c's output to the build script's stderr on its own.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .linker_arguments(["--enable-linker-version", "-EB"])
+    ///     .verbose(true)
+    ///     .compiler_verbosity(3)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn linker_arguments<P>(&mut self, linker_arguments: P) -> &mut Self
-    where
-        P: IntoIterator,
-        P::Item: Into<String>,
-    {
-        for linker_argument in linker_arguments {
-            let linker_argument = linker_argument.into() as String;
-            self.linker_argument(&linker_argument);
-        }
-
+    pub fn compiler_verbosity(&mut self, level: u8) -> &mut Self {
+        self.compiler_verbosity = Some(level);
         self
     }
 
-    /// Sets an environment variable.
+    /// Whether c3c should also write the LLVM IR of the compiled C3 code, next to building the library.
+    ///
+    /// Default: false.
     ///
-    /// Equivalent to, on Unix systems, calling c3c with an environment variable beforehand `FOO=BAR c3c ...`.
+    /// When `true`, this is equivalent to calling c3c with `--emit-llvm`. The `.ll` files are written to
+    /// [C3FFI::llvm_ir_dir] and reflect the [C3FFI::optimization_level] in use, so the IR of different levels can be
+    /// compared by building with each.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .environment_variable(("FOO", "BAR"))
+    ///     .emit_llvm(true)
+    ///     .optimization_level(c3ne::OptimizationLevel::O2)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn environment_variable(&mut self, environment_variable: (&str, &str)) -> &mut Self {
-        let environment_variable = (
-            environment_variable.0.to_string(),
-            environment_variable.1.to_string(),
-        );
-
-        if !self.environment_variables.contains(&environment_variable) {
-            self.environment_variables.push(environment_variable);
-        }
+    pub fn emit_llvm(&mut self, emit_llvm: bool) -> &mut Self {
+        self.emit_llvm = emit_llvm;
         self
     }
 
-    /// Sets one or more environment variables.
+    /// Whether c3c should also write the assembly of the compiled C3 code, next to building the library.
     ///
-    /// Equivalent to, on Unix systems, calling c3c with several environment variables beforehand `VAR_1=FOO [VAR_2=BAR ... VAR_N=FOOBAR] c3c ...`.
+    /// Default: false.
+    ///
+    /// When `true`, this is equivalent to calling c3c with `--emit-asm`. The `.s` files are written to
+    /// [C3FFI::asm_dir]; the library is still built and linked as usual.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .environment_variables([("VAR_1", "FOO"), ("VAR_2", "BAR")])
+    ///     .emit_asm(true)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn environment_variables<V, P>(&mut self, environment_variables: P) -> &mut Self
-    where
-        V: Into<String>,
-        P: IntoIterator,
-        P::Item: Into<(V, V)>,
-    {
-        for environment_variable in environment_variables {
-            let environment_variable: (V, V) = environment_variable.into();
-            let environment_variable: (String, String) =
-                (environment_variable.0.into(), environment_variable.1.into());
-
-            self.environment_variable((&environment_variable.0, &environment_variable.1));
-        }
-
+    pub fn emit_asm(&mut self, emit_asm: bool) -> &mut Self {
+        self.emit_asm = emit_asm;
         self
     }
 
-    /// Marks a directory as containing compiled libraries.
+    /// The relocation model used for the library's machine code, see [RelocModel].
+    ///
+    /// Default: unset, leaving the choice to c3c.
     ///
-    /// Equivalent to calling c3c with `-L <dir>`.
+    /// Rust links executables as PIE and requires position-independent code in `cdylib`s by default, so a static library
+    /// linked into Rust should use [RelocModel::Pic] or [RelocModel::Pie]. [RelocModel::Static] produces code that fails to
+    /// link, or needs text relocations, in that case.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .compiled_lib_dir("libs")
+    ///     .reloc_model(RelocModel::Pic)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn compiled_lib_dir<P>(&mut self, compiled_lib_dir: P) -> &mut Self
-    where
-        P: Into<PathBuf>,
-    {
-        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
-        let compiled_lib_dir = compiled_lib_dir.into() as PathBuf;
-        if !self.compiled_lib_dirs.contains(&compiled_lib_dir) {
-            self.compiled_lib_dirs.push(compiled_lib_dir);
-        }
-
+    pub fn reloc_model(&mut self, reloc_model: RelocModel) -> &mut Self {
+        self.reloc_model = Some(reloc_model);
         self
     }
 
-    /// Marks one or more directories as containing compiled libraries.
+    /// Instruments the library with a sanitizer, see [Sanitizer], for fuzzing or hunting memory bugs.
     ///
-    /// Equivalent to calling c3c with `-L <dir1> [-L <dir2> ... -L <dirN>]`.
+    /// Default: unset.
+    ///
+    /// Besides the c3c flag, `cargo::rustc-link-arg=-fsanitize=<kind>` is emitted so the sanitizer's runtime is linked
+    /// into the final binary, which requires a GCC or Clang style linker. Sanitizers are meant for debug builds: use
+    /// [OptimizationLevel::O0] or [OptimizationLevel::O1], since higher levels optimize away much of what they would
+    /// catch and [OptimizationLevel::O4] and above remove the safety checks they build on.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .compiled_lib_dirs(["libs", &format("{}/.local.libs", env!("HOME"))])
+    ///     .sanitizer(Sanitizer::Address)
+    ///     .optimization_level(OptimizationLevel::O1)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn compiled_lib_dirs<P>(&mut self, compiled_lib_dirs: P) -> &mut Self
-    where
-        P: IntoIterator,
-        P::Item: Into<PathBuf>,
-    {
-        for compiled_lib_dir in compiled_lib_dirs {
-            self.compiled_lib_dir(compiled_lib_dir.into());
-        }
-
+    pub fn sanitizer(&mut self, sanitizer: Sanitizer) -> &mut Self {
+        self.sanitizer = Some(sanitizer);
         self
     }
 
-    /// Marks a file as a compiled library.
+    /// The object file format the library is expected to be in, see [ObjectFormat].
     ///
-    /// Equivalent to calling c3c with `-l <lib>`.
+    /// Default: unset.
+    ///
+    /// c3c has no flag for the object format and always derives it from the target, so this does not change what is
+    /// emitted. It only asserts that the resolved target produces the given format, e.g. [ObjectFormat::Coff] for
+    /// Windows, and fails compilation with [C3Error::InvalidConfiguration] otherwise, catching a cross-compilation
+    /// that silently ends up targeting the wrong platform.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .compiled_lib("somelib")
+    ///     .object_format(ObjectFormat::Elf)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn compiled_lib<P>(&mut self, compiled_lib: P) -> &mut Self
-    where
-        P: Into<PathBuf>,
-    {
-        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
-        let compiled_lib = compiled_lib.into() as PathBuf;
-        if !self.compiled_libs.contains(&compiled_lib) {
-            self.compiled_libs.push(compiled_lib);
-        }
-
+    pub fn object_format(&mut self, object_format: ObjectFormat) -> &mut Self {
+        self.object_format = Some(object_format);
         self
     }
 
-    /// Marks one or more files as a compiled library.
+    /// The libc implementation the library is compiled and linked against, see [LibcKind].
     ///
-    /// Equivalent to calling c3c with `-l <lib1> [-l <lib2> ... -l <libN>]`.
+    /// Default: unset, leaving the choice to c3c and the Rust target.
+    ///
+    /// The choice must agree with the Rust target, e.g. [LibcKind::Musl] requires a `*-musl*` target, otherwise
+    /// compilation fails with [C3Error::InvalidConfiguration]. Besides the c3c flag, a `rustc-link-lib` directive for
+    /// libc is emitted after the library's own.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .compiled_libs(["somelib", "otherlib"])
+    ///     .libc(LibcKind::Musl)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn compiled_libs<P>(&mut self, compiled_libs: P) -> &mut Self
-    where
-        P: IntoIterator,
-        P::Item: Into<PathBuf>,
-    {
-        for compiled_lib in compiled_libs {
-            self.compiled_lib(compiled_lib.into());
-        }
-
+    pub fn libc(&mut self, kind: LibcKind) -> &mut Self {
+        self.libc = Some(kind);
         self
     }
 
-    /// Marks a directory as containing C3 libraries.
+    /// How the library reacts to a C3 panic, see [PanicStrategy].
+    ///
+    /// Default: [PanicStrategy::Default].
     ///
-    /// Equivalent to calling c3c with `--libdir <dir>`.
+    /// This should match the `panic` setting of the Rust profile. C3 panics never unwind into Rust: with either strategy
+    /// the process is terminated, [PanicStrategy::Unwind] only makes sure the panic message reaches stderr first, the
+    /// same way a Rust panic under `panic = "unwind"` reports itself before the runtime aborts at the FFI boundary.
+    /// Mixing strategies is therefore ABI-safe, but one side of the program will abort silently while the other
+    /// reports its panics.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .c3_lib_dir("libs")
+    ///     .panic_strategy(PanicStrategy::Abort)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn c3_lib_dir<P>(&mut self, c3_lib_dir: P) -> &mut Self
-    where
-        P: Into<PathBuf>,
-    {
-        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
-        let c3_lib_dir = c3_lib_dir.into() as PathBuf;
-        if !self.c3_lib_dirs.contains(&c3_lib_dir) {
-            self.c3_lib_dirs.push(c3_lib_dir);
-        }
-
+    pub fn panic_strategy(&mut self, panic_strategy: PanicStrategy) -> &mut Self {
+        self.panic_strategy = panic_strategy;
         self
     }
 
-    /// Marks one or more directories as containing C3 libraries.
+    /// Whether C3 panics print their message, regardless of the optimization level.
     ///
-    /// Equivalent to calling c3c with `--libdir <dir1> [--libdir <dir2> ... --libdir <dirN>]`.
+    /// Default: unset, leaving it to the optimization level, which turns panic messages off from
+    /// [OptimizationLevel::O4] on.
+    ///
+    /// Equivalent to calling c3c with `--panic-msg=yes` or `--panic-msg=no`. Takes precedence over
+    /// [C3FFI::panic_strategy], which controls the same flag.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .c3_lib_dirs(["libs", &format("{}/.local.libs", env!("HOME"))])
+    ///     .optimization_level(OptimizationLevel::O2)
+    ///     .panic_messages(true)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn c3_lib_dirs<P>(&mut self, c3_lib_dirs: P) -> &mut Self
-    where
-        P: IntoIterator,
-        P::Item: Into<PathBuf>,
-    {
-        for c3_lib_dir in c3_lib_dirs {
-            self.c3_lib_dir(c3_lib_dir.into());
-        }
-
+    pub fn panic_messages(&mut self, panic_messages: bool) -> &mut Self {
+        self.panic_messages = Some(panic_messages);
         self
     }
 
-    /// Marks a file as a C3 library.
+    /// Whether C3 panics print a backtrace, regardless of the optimization level.
+    ///
+    /// Default: unset, leaving it to the optimization level, which turns backtraces off from [OptimizationLevel::O4] on.
     ///
-    /// Equivalent to calling c3c with `--lib <lib>`.
+    /// Equivalent to calling c3c with `--show-backtrace=yes` or `--show-backtrace=no`.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .c3_lib("somelib")
+    ///     .optimization_level(OptimizationLevel::O2)
+    ///     .backtrace(true)
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn c3_lib<P>(&mut self, c3_lib: P) -> &mut Self
-    where
-        P: Into<PathBuf>,
-    {
-        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
-        let c3_lib = c3_lib.into() as PathBuf;
-        if !self.c3_libs.contains(&c3_lib) {
-            self.c3_libs.push(c3_lib);
-        }
-
+    pub fn backtrace(&mut self, backtrace: bool) -> &mut Self {
+        self.backtrace = Some(backtrace);
         self
     }
 
-    /// Marks one or more files as a C3 library.
+    /// Marks a file as target for compilation.
     ///
-    /// Equivalent to calling c3c with `--lib <lib1> [--lib <lib2> ... --lib <libN>]`.
+    /// Equivalent to calling c3c with the path to a source file.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .c3_libs(["somelib", "otherlib"])
     ///     .file("extern/thing.c3")
     ///     .compile("thing");
     /// ```
-    pub fn c3_libs<P>(&mut self, c3_libs: P) -> &mut Self
+    pub fn file<P>(&mut self, file: P) -> &mut Self
     where
-        P: IntoIterator,
-        P::Item: Into<PathBuf>,
+        P: Into<PathBuf>,
     {
-        for c3_lib in c3_libs {
-            self.c3_lib(c3_lib.into());
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let file = file.into() as PathBuf;
+        if !self.files.contains(&file) {
+            println!("cargo::rerun-if-changed={}", file.display());
+            self.files.push(file);
         }
 
         self
     }
 
-    /// Attempts to compile the provided C3 source files, panicking if it fails to do so.
+    /// Marks one or more files for compilation.
+    ///
+    /// Equivalent to calling c3c with the path to several source files.
     ///
     ///
     /// Example:
     /// ```rs
     /// c3ne::C3FFI::new()
-    ///     .file("extern/thing.c3")
+    ///     .files(["extern/thingmabob.c3", "extern/thingmajane.c3"])
     ///     .compile("thing");
     /// ```
-    pub fn compile(&mut self, name: &str) {
-        if let Err(err) = self.attempt_compilation(name) {
-            panic!("{}", err);
+    pub fn files<P>(&mut self, files: P) -> &mut Self
+    where
+        P: IntoIterator,
+        P::Item: Into<PathBuf>,
+    {
+        for file in files {
+            self.file(file.into());
         }
+
+        self
     }
 
-    /// Attempts to compile the provided C3 source files, returning an error if it fails to do so.
+    /// Marks every file listed in a text file for compilation, one path per line, for projects that keep their source
+    /// list outside of the build script.
+    ///
+    /// Blank lines and lines starting with `#` are ignored, and relative paths are resolved against the directory of the
+    /// listing. The build script re-runs whenever the listing changes.
     ///
     ///
     /// Example:
     /// ```rs
-    /// if let Err(err) = c3ne::C3FFInew()
-    ///     .files(["extern/thingmabob.c3", "extern/thingmajane.c3"])
-    ///     .attempt_compilation("thing")
-    /// {
-    ///     panic!("{}", err);
-    /// }
+    /// c3ne::C3FFI::new()
+    ///     .files_from_list("extern/sources.txt")?
+    ///     .compile("thing");
     /// ```
-    pub fn attempt_compilation(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
-        let wasm_targets = vec!["wasm32"];
-        let mingw_toolchains = vec!["gnu", "gnullvm"];
+    pub fn files_from_list<P>(&mut self, list: P) -> Result<&mut Self, C3Error>
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let list = list.into() as PathBuf;
+        println!("cargo::rerun-if-changed={}", list.display());
 
-        let target = var("TARGET")?;
-        let target_split: Vec<&str> = target.split("-").collect();
-        let where_os = if (&target_split).len() == 4 {
-            2
-        } else {
-            if wasm_targets.contains(&target_split[0]) {
-                0
-            } else {
-                1usize
+        let list_dir = list.parent().unwrap_or(Path::new("")).to_path_buf();
+        for line in fs::read_to_string(&list)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
             }
-        };
-        let mut architecture = target_split[0];
-        let mut os = target_split[where_os];
-        let is_wasm = wasm_targets.contains(&os);
-        let toolchain = target_split[where_os + 1];
-        if os.eq_ignore_ascii_case("windows") && mingw_toolchains.contains(&toolchain) {
-            os = "mingw";
-        }
-        if architecture.eq_ignore_ascii_case("x86_64") {
-            architecture = "x64";
-        }
-        let c3_target = if is_wasm {
-            String::from_str("wasm32").unwrap()
-        } else {
-            format!("{}-{}", os, architecture)
-        };
 
-        let out_dir = &var("OUT_DIR")?;
-
-        let command_corresponding_linking = if is_wasm {
-            "compile"
-        } else {
-            match self.linking_mode {
-                LinkingMode::Static => "static-lib",
-                LinkingMode::Dynamic => "dynamic-lib",
-            }
-        };
-        let debug_flag = format!("-g{}", if self.debug_info { "" } else { "0" });
-        let optimization_level_flag = format!("-{}", self.optimization_level.to_str());
-        let out_name: String = format!("{}{}", if is_wasm { "" } else { "lib" }, name);
-
-        let args = {
-            let mut args: Vec<&str> = Vec::new();
-
-            args.push(command_corresponding_linking);
-            args.push(&debug_flag);
-            args.push(&optimization_level_flag);
-            args.push("--output-dir");
-            args.push(out_dir);
-            args.push("-o");
-            args.push(&out_name);
-            args.push("--target");
-            args.push(&c3_target);
-            for feature in &self.features {
-                args.push("-D");
-                args.push(&feature);
-            }
-            for linker_argument in &self.linker_arguments {
-                args.push("-z");
-                args.push(&linker_argument);
-            }
-            for c3_lib_dir in &self.c3_lib_dirs {
-                args.push("--libdir");
-                args.push(c3_lib_dir.as_os_str().to_str().unwrap());
-            }
-            for c3_lib in &self.c3_libs {
-                args.push("--lib");
-                args.push(c3_lib.as_os_str().to_str().unwrap());
-            }
-
-            if !is_wasm {
-                for compiled_lib_dir in &self.compiled_lib_dirs {
-                    args.push("-L");
-                    args.push(compiled_lib_dir.as_os_str().to_str().unwrap());
-                }
-                for compiled_lib in &self.compiled_libs {
-                    args.push("-l");
-                    args.push(compiled_lib.as_os_str().to_str().unwrap());
-                }
-            } else {
-                args.append(&mut vec![
-                    "--link-libc=no",
-                    "--use-stdlib=yes",
-                    "--no-entry",
-                    "-z",
-                    "--relocatable"
-                ]);
-            }
+            self.file(list_dir.join(line));
+        }
 
-            for file in &self.files {
-                args.push(file.as_os_str().to_str().unwrap());
-            }
-            for arg in &self.args {
-                args.push(&arg);
-            }
+        Ok(self)
+    }
 
-            args
-        };
+    /// Treats files with the given extension, without the leading dot, as C3 sources.
+    ///
+    /// c3c only accepts its own extensions, so such files are copied to `OUT_DIR/c3-sources` under a `.c3` name before
+    /// compiling, keeping their path so files with the same name in different directories stay apart, e.g.
+    /// `gen/thing.c3gen` becomes `c3-sources/gen/thing.c3gen.c3`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .treat_as_c3("c3gen")
+    ///     .file("gen/thing.c3gen")
+    ///     .compile("thing");
+    /// ```
+    pub fn treat_as_c3(&mut self, ext: &str) -> &mut Self {
+        let ext = ext.trim_start_matches('.').to_string();
+        if !self.c3_extensions.contains(&ext) {
+            self.c3_extensions.push(ext);
+        }
+        self
+    }
 
-        let mut environment_variables: HashMap<String, String> = HashMap::new();
-        for (key, value) in &self.environment_variables {
-            environment_variables.insert(key.clone(), value.clone());
+    /// Adds a source file generated by the build script, given as raw bytes.
+    ///
+    /// The bytes are written to `OUT_DIR/c3-sources/<name>` when compiling, which is then compiled like any file passed to
+    /// [C3FFI::file]. The file is only rewritten when its contents change, so unchanged generated sources keep their
+    /// timestamps.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .source_bytes("generated.c3", &template.render())
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn source_bytes(&mut self, name: &str, bytes: &[u8]) -> &mut Self {
+        match self
+            .generated_sources
+            .iter_mut()
+            .find(|(existing_name, _)| existing_name == name)
+        {
+            Some(existing) => existing.1 = bytes.to_vec(),
+            None => self.generated_sources.push((name.to_string(), bytes.to_vec())),
         }
 
-        match Command::new(&self.compiler)
-            .args(args.clone())
-            .envs(environment_variables)
-            .output() {
-            Ok(_) => {},
-            Err(err) => panic!("{}", err),
+        self
+    }
+
+    /// Turns on a feature for the provided source files.
+    ///
+    /// Equivalent to calling c3c with `-D <feature>`. The feature must be a bare name, e.g. `foo` rather than `-D foo`,
+    /// otherwise compilation fails with [C3Error::InvalidConfiguration].
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .feature("foo")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn feature(&mut self, feature: &str) -> &mut Self {
+        if !self.features.contains(&feature.to_string()) {
+            self.features.push(feature.to_string());
         }
+        self
+    }
 
-        if !is_wasm {
-            println!("cargo:rustc-link-search=native={}", out_dir);
-            println!("cargo:rustc-link-lib=static={}", name);
-        } else {
-            println!(
-                "cargo:rustc-link-arg={}",
-                Path::new(out_dir)
-                    .join(out_name.clone() + ".wasm")
-                    .as_os_str()
-                    .to_str()
-                    .unwrap()
-            );
+    /// Turns on one or more features for the provided source files.
+    ///
+    /// Equivalent to calling c3c with `-D <feature1> [-D <feature2> ... -D <featureN>]`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .features(["foo", "bar"])
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn features<P>(&mut self, features: P) -> &mut Self
+    where
+        P: IntoIterator,
+        P::Item: Into<String>,
+    {
+        for feature in features {
+            let feature = feature.into() as String;
+            self.feature(&feature);
         }
 
-        Ok(())
+        self
     }
-}
 
-pub enum LinkingMode {
-    /// Equivalent to calling c3c with the `static-lib` command.
-    Static,
-    /// Equivalent to calling c3c with the `dynamic-lib` command.
-    Dynamic,
-}
+    /// Declares two features as mutually exclusive, failing the compilation with [C3Error::ConflictingFeatures] when
+    /// both are turned on, instead of letting c3c run into whatever breaks when they are combined.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .conflicting_features("BACKEND_SSE", "BACKEND_NEON")
+    ///     .feature("BACKEND_SSE")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn conflicting_features(&mut self, a: &str, b: &str) -> &mut Self {
+        self.conflicting_features.push((a.to_string(), b.to_string()));
+        self
+    }
 
-pub enum OptimizationLevel {
-    /// Safe, no optimizations, emit debug info.
+    /// Adds a custom argument to be passed to the compiler.
     ///
-    /// Equivalent to calling c3c with `-O0`.
-    O0,
-    /// Safe, high optimization, emit debug info.
+    /// Arguments that a dedicated method covers, such as `-O2` for [C3FFI::optimization_level], emit a cargo warning.
     ///
-    /// Equivalent to calling c3c with `-O1`.
-    O1,
-    /// Unsafe, high optimization, emit debug info.
     ///
-    /// Equivalent to calling c3c with `-O2`.
-    O2,
-    /// Unsafe, high optimization, single module, emit debug info.
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .arg("--no-headers")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn arg(&mut self, arg: &str) -> &mut Self {
+        if !self.args.contains(&arg.to_string()) {
+            self.args.push(arg.to_string());
+        }
+        self
+    }
+
+    /// Adds one or more custom arguments to be passed to the compiler.
     ///
-    /// Equivalent to calling c3c with `-O3`.
-    O3,
-    /// Unsafe, highest optimization, relaxed maths, single module, emit debug info, no panic messages.
     ///
-    /// Equivalent to calling c3c with `-O4`.
-    O4,
-    /// Unsafe, highest optimization, fast maths, single module, emit debug info, no panic messages, no backtrace.
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .args(["--no-headers", "--use-old-enums"])
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn args<P>(&mut self, args: P) -> &mut Self
+    where
+        P: IntoIterator,
+        P::Item: Into<String>,
+    {
+        for arg in args {
+            let arg = arg.into() as String;
+            self.arg(&arg);
+        }
+
+        self
+    }
+
+    /// Lets the final arguments passed to c3c be changed right before it is run, as a last resort for anything the
+    /// other methods do not cover.
     ///
-    /// Equivalent to calling c3c with `-O5`.
-    O5,
-    /// Unsafe, high optimization, small code, single module, no debug info, no panic messages.
+    /// The callback receives every argument, excluding the compiler itself. It is kept for every later compilation of
+    /// the builder and called once per c3c invocation, so once per library with [C3FFI::compile_many] and
+    /// [C3FFI::split_output], and can keep state across calls. Setting another callback replaces it.
     ///
-    /// Equivalent to calling c3c with `-Os`.
-    Os,
-    /// Unsafe, high optimization, tiny code, single module, no debug info, no panic messages, no backtrace.
     ///
-    /// Equivalent to calling c3c with `-Oz`.
-    Oz,
-}
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .modify_args(|args| args.retain(|arg| arg != "-g"))
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn modify_args<F>(&mut self, modify_args: F) -> &mut Self
+    where
+        F: FnMut(&mut Vec<String>) + 'static,
+    {
+        self.args_modifier = Some(Box::new(modify_args));
+        self
+    }
 
-impl OptimizationLevel {
-    pub fn to_str(&self) -> &str {
-        match self {
-            OptimizationLevel::O0 => "O0",
-            OptimizationLevel::O1 => "O1",
-            OptimizationLevel::O2 => "O2",
-            OptimizationLevel::O3 => "O3",
-            OptimizationLevel::O4 => "O4",
-            OptimizationLevel::O5 => "O5",
-            OptimizationLevel::Os => "Os",
-            OptimizationLevel::Oz => "Oz",
-        }
+    /// Runs a command, e.g. a formatter or linter, over the registered source files before compiling, failing with
+    /// [C3Error::PrebuildFailed] if it does not succeed.
+    ///
+    /// The paths to the source files are appended to `args`. Several commands can be registered, which run in order.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .prebuild_command("c3fmt", &["--check"])
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn prebuild_command(&mut self, program: &str, args: &[&str]) -> &mut Self {
+        self.prebuild_commands.push((
+            program.to_string(),
+            args.iter().map(|arg| arg.to_string()).collect(),
+        ));
+        self
     }
-}
 
-/// Alternative name for [C3FFI], provided for users looking for a more standard naming approach.
-pub type Build = C3FFI;
+    /// Marks an argument to be passed to the linker.
+    ///
+    /// Equivalent to calling c3c with `-z <arg>`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .linker_argument("--enable-linker-version")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn linker_argument(&mut self, linker_argument: &str) -> &mut Self {
+        if !self.linker_arguments.contains(&linker_argument.to_string()) {
+            self.linker_arguments.push(linker_argument.to_string());
+        }
+        self
+    }
+
+    /// Marks one or more arguments to be passed to the linker.
+    ///
+    /// Equivalent to calling c3c with `-z <arg1> [-z <arg2> ... -z <argN>]`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .linker_arguments(["--enable-linker-version", "-EB"])
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn linker_arguments<P>(&mut self, linker_arguments: P) -> &mut Self
+    where
+        P: IntoIterator,
+        P::Item: Into<String>,
+    {
+        for linker_argument in linker_arguments {
+            let linker_argument = linker_argument.into() as String;
+            self.linker_argument(&linker_argument);
+        }
+
+        self
+    }
+
+    /// Sets an environment variable.
+    ///
+    /// Equivalent to, on Unix systems, calling c3c with an environment variable beforehand `FOO=BAR c3c ...`. The build
+    /// script re-runs when a variable of the same name changes in its own environment, since values are often forwarded
+    /// from there.
+    ///
+    /// Setting a variable again replaces its value. Names are compared case-insensitively on Windows, like Windows itself
+    /// does, so `PATH` and `Path` are the same variable there.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .environment_variable(("FOO", "BAR"))
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn environment_variable(&mut self, environment_variable: (&str, &str)) -> &mut Self {
+        let environment_variable = (
+            environment_variable.0.to_string(),
+            environment_variable.1.to_string(),
+        );
+
+        match self
+            .environment_variables
+            .iter_mut()
+            .find(|(key, _)| *key == environment_variable.0)
+        {
+            Some(existing) => *existing = environment_variable,
+            None => {
+                println!("cargo::rerun-if-env-changed={}", environment_variable.0);
+                self.environment_variables.push(environment_variable);
+            }
+        }
+        self
+    }
+
+    /// Sets one or more environment variables.
+    ///
+    /// Equivalent to, on Unix systems, calling c3c with several environment variables beforehand `VAR_1=FOO [VAR_2=BAR ... VAR_N=FOOBAR] c3c ...`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .environment_variables([("VAR_1", "FOO"), ("VAR_2", "BAR")])
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn environment_variables<V, P>(&mut self, environment_variables: P) -> &mut Self
+    where
+        V: Into<String>,
+        P: IntoIterator,
+        P::Item: Into<(V, V)>,
+    {
+        for environment_variable in environment_variables {
+            let environment_variable: (V, V) = environment_variable.into();
+            let environment_variable: (String, String) =
+                (environment_variable.0.into(), environment_variable.1.into());
+
+            self.environment_variable((&environment_variable.0, &environment_variable.1));
+        }
+
+        self
+    }
+
+    /// Sets an environment variable only for the given [Phase], taking precedence over one set with
+    /// [C3FFI::environment_variable] under the same name.
+    ///
+    /// Setting the same variable for the same phase again replaces its value.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .phase_env(Phase::VerifyLink, "LD_LIBRARY_PATH", "extern/lib")
+    ///     .verify_link(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn phase_env(&mut self, phase: Phase, key: &str, value: &str) -> &mut Self {
+        match self
+            .phase_environment_variables
+            .iter_mut()
+            .find(|(existing_phase, existing_key, _)| *existing_phase == phase && existing_key == key)
+        {
+            Some((_, _, existing_value)) => *existing_value = value.to_string(),
+            None => {
+                println!("cargo::rerun-if-env-changed={}", key);
+                self.phase_environment_variables
+                    .push((phase, key.to_string(), value.to_string()));
+            }
+        }
+        self
+    }
+
+    /// Whether environment variable names that differ only in case refer to the same variable, so setting `Path` after
+    /// `PATH` replaces it instead of adding a second variable.
+    ///
+    /// Default: true when building for Windows, according to `CARGO_CFG_TARGET_OS`, and false otherwise.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .case_insensitive_env(true)
+    ///     .environment_variable(("PATH", "C:\\c3"))
+    ///     .environment_variable(("Path", "C:\\c3\\bin"))
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn case_insensitive_env(&mut self, case_insensitive_env: bool) -> &mut Self {
+        self.case_insensitive_env = Some(case_insensitive_env);
+        self
+    }
+
+    /// Sets the default stack size, in bytes, recorded in the library.
+    ///
+    /// Only dynamic libraries for ELF targets (Linux, Android and the BSDs) can carry a stack size, where this is
+    /// equivalent to calling [C3FFI::linker_argument] with `-zstack-size=<bytes>`. Other targets emit a cargo warning
+    /// and ignore the setting.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .linking_mode(LinkingMode::Dynamic)
+    ///     .stack_size(8 * 1024 * 1024)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn stack_size(&mut self, bytes: u64) -> &mut Self {
+        self.stack_size = Some(bytes);
+        self
+    }
+
+    /// Marks symbols which the linker must keep, even if they look unused and would otherwise be stripped.
+    ///
+    /// Equivalent to calling c3c with `-z --undefined=<symbol>` for ELF and MinGW targets, `-z -u -z _<symbol>` for
+    /// Apple targets, `-z /INCLUDE:<symbol>` for MSVC targets, and `-z --export=<symbol>` for WebAssembly.
+    ///
+    /// Static libraries are never linked by c3c, so there is nothing to keep the symbols in, and compilation fails with
+    /// [C3Error::InvalidConfiguration] in that case.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .linking_mode(LinkingMode::Dynamic)
+    ///     .keep_symbols(&["plugin_entry", "plugin_version"])
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn keep_symbols(&mut self, symbols: &[&str]) -> &mut Self {
+        for symbol in symbols {
+            if !self.kept_symbols.contains(&symbol.to_string()) {
+                self.kept_symbols.push(symbol.to_string());
+            }
+        }
+
+        self
+    }
+
+    /// Whether every function and global is kept in the library, even when nothing references it, e.g. so coverage
+    /// tools can report functions that are never called.
+    ///
+    /// Default: false.
+    ///
+    /// Equivalent to calling c3c with `--no-strip-unused`. Unlike [C3FFI::keep_symbols], this applies to all code and
+    /// happens during code generation rather than linking, which can noticeably grow the library, especially with the
+    /// standard library's unused generic instances.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .link_dead_code(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn link_dead_code(&mut self, link_dead_code: bool) -> &mut Self {
+        self.link_dead_code = link_dead_code;
+        self
+    }
+
+    /// Builds the c3c project in the given directory instead of individual source files.
+    ///
+    /// Equivalent to calling c3c with `build` from within the project directory. Source files registered with
+    /// [C3FFI::file] are ignored, since the project's `project.json` lists its own, and relative paths given to other
+    /// methods are resolved from the project directory.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .project("extern/thing")
+    ///     .compile("thing");
+    /// ```
+    pub fn project<P>(&mut self, project: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let project = project.into() as PathBuf;
+        println!("cargo::rerun-if-changed={}", project.display());
+        self.project = Some(project);
+        self
+    }
+
+    /// Selects which of the project's build targets is built, only valid together with [C3FFI::project].
+    ///
+    /// Equivalent to calling c3c with `build <name>`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .project("extern/thing")
+    ///     .build_config("thing-release")
+    ///     .compile("thing");
+    /// ```
+    pub fn build_config(&mut self, name: &str) -> &mut Self {
+        self.build_config = Some(name.to_string());
+        self
+    }
+
+    /// Sets environment variables from a `.env` file, as if each were passed to [C3FFI::environment_variable].
+    ///
+    /// Every non-empty line not starting with `#` must be of the form `KEY=VALUE`, optionally prefixed with `export`.
+    /// Values may be wrapped in single or double quotes, the latter supporting `\n`, `\"` and `\\` escapes. When a key
+    /// appears more than once, the last value wins.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .env_file(".env")?
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn env_file<P>(&mut self, env_file: P) -> Result<&mut Self, C3Error>
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let env_file = env_file.into() as PathBuf;
+        println!("cargo::rerun-if-changed={}", env_file.display());
+
+        let mut environment_variables: Vec<(String, String)> = Vec::new();
+        for (index, line) in fs::read_to_string(&env_file)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(C3Error::InvalidConfiguration(format!(
+                    "{}:{}: expected KEY=VALUE",
+                    env_file.display(),
+                    index + 1
+                )));
+            };
+
+            let key = key.trim().to_string();
+            let value = parse_env_value(value.trim());
+            environment_variables.retain(|(existing_key, _)| *existing_key != key);
+            environment_variables.push((key, value));
+        }
+
+        for (key, value) in &environment_variables {
+            self.environment_variable((key, value));
+        }
+
+        Ok(self)
+    }
+
+    /// The sysroot the linker searches for system libraries, for cross-compilation setups where it differs from the one
+    /// used while compiling.
+    ///
+    /// Equivalent to calling c3c with `-z --sysroot=<dir>`, or `-z -syslibroot -z <dir>` for Apple targets. MSVC's linker
+    /// has no sysroot, and static libraries are never linked by c3c, so both ignore it with a cargo warning.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .linking_mode(LinkingMode::Dynamic)
+    ///     .link_sysroot("/opt/sysroots/aarch64-linux-gnu")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn link_sysroot<P>(&mut self, link_sysroot: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let link_sysroot = link_sysroot.into() as PathBuf;
+        println!("cargo::rerun-if-changed={}", link_sysroot.display());
+        self.link_sysroot = Some(link_sysroot);
+        self
+    }
+
+    /// The macOS SDK c3c uses to compile and link for Apple targets, needed when cross-compiling from another OS.
+    ///
+    /// Equivalent to calling c3c with `--macossdk <dir>`. Compilation fails with [C3Error::InvalidConfiguration] if the
+    /// directory does not exist.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .macos_sdk("/opt/MacOSX14.sdk")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn macos_sdk<P>(&mut self, macos_sdk: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let macos_sdk = macos_sdk.into() as PathBuf;
+        self.macos_sdk = Some(macos_sdk);
+        self
+    }
+
+    /// The Windows SDK c3c uses to link for MSVC targets, needed when it cannot find one itself, e.g. on CI or when
+    /// cross-compiling.
+    ///
+    /// Equivalent to calling c3c with `--winsdk <dir>`. Ignored, with a cargo warning, for targets other than MSVC.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .windows_sdk("C:/Program Files (x86)/Windows Kits/10")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn windows_sdk<P>(&mut self, windows_sdk: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let windows_sdk = windows_sdk.into() as PathBuf;
+        self.windows_sdk = Some(windows_sdk);
+        self
+    }
+
+    /// The C runtime the library is linked against on MSVC targets, see [WinCrt].
+    ///
+    /// Default: unset, leaving the choice to c3c.
+    ///
+    /// Ignored, with a cargo warning, for targets other than MSVC. Should match the CRT Rust links against, which is
+    /// [WinCrt::Dynamic] unless the `crt-static` target feature is enabled.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .windows_crt(WinCrt::Static)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn windows_crt(&mut self, windows_crt: WinCrt) -> &mut Self {
+        self.windows_crt = Some(windows_crt);
+        self
+    }
+
+    /// Whether the `rustc-link-search` directive for the library uses a path relative to `CARGO_MANIFEST_DIR` instead of
+    /// the absolute path of `OUT_DIR`.
+    ///
+    /// Default: false.
+    ///
+    /// Relative search paths are resolved by rustc against its working directory, which cargo sets to the package
+    /// directory for standalone packages but to the workspace root for workspace members. Cargo generally expects absolute
+    /// paths, so only use this when the absolute path of `OUT_DIR` differs between the build script and rustc, e.g. in
+    /// some sandboxes, and the crate is built as a standalone package. If `OUT_DIR` and `CARGO_MANIFEST_DIR` share no
+    /// common root, such as different drives on Windows, the absolute path is used anyway.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .relative_link_search(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn relative_link_search(&mut self, relative_link_search: bool) -> &mut Self {
+        self.relative_link_search = relative_link_search;
+        self
+    }
+
+    /// Marks a directory as containing compiled libraries.
+    ///
+    /// Equivalent to calling c3c with `-L <dir>`. The build script re-runs whenever anything in the directory changes.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .compiled_lib_dir("libs")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn compiled_lib_dir<P>(&mut self, compiled_lib_dir: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let compiled_lib_dir = compiled_lib_dir.into() as PathBuf;
+        if !self.compiled_lib_dirs.contains(&compiled_lib_dir) {
+            println!("cargo::rerun-if-changed={}", compiled_lib_dir.display());
+            self.compiled_lib_dirs.push(compiled_lib_dir);
+        }
+
+        self
+    }
+
+    /// Marks one or more directories as containing compiled libraries.
+    ///
+    /// Equivalent to calling c3c with `-L <dir1> [-L <dir2> ... -L <dirN>]`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .compiled_lib_dirs(["libs", &format("{}/.local.libs", env!("HOME"))])
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn compiled_lib_dirs<P>(&mut self, compiled_lib_dirs: P) -> &mut Self
+    where
+        P: IntoIterator,
+        P::Item: Into<PathBuf>,
+    {
+        for compiled_lib_dir in compiled_lib_dirs {
+            self.compiled_lib_dir(compiled_lib_dir.into());
+        }
+
+        self
+    }
+
+    /// Marks a directory as containing compiled libraries, searching it before every directory added so far.
+    ///
+    /// Equivalent to calling c3c with `-L <dir>` before any other `-L`. Useful when several directories contain a
+    /// library with the same name.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .compiled_lib_dir("libs")
+    ///     .prepend_compiled_lib_dir("patched-libs")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn prepend_compiled_lib_dir<P>(&mut self, compiled_lib_dir: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let compiled_lib_dir = compiled_lib_dir.into() as PathBuf;
+        if !self.compiled_lib_dirs.contains(&compiled_lib_dir) {
+            println!("cargo::rerun-if-changed={}", compiled_lib_dir.display());
+        }
+        self.compiled_lib_dirs.retain(|existing| *existing != compiled_lib_dir);
+        self.compiled_lib_dirs.insert(0, compiled_lib_dir);
+
+        self
+    }
+
+    /// Marks a file as a compiled library.
+    ///
+    /// Equivalent to calling c3c with `-l <lib>`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .compiled_lib("somelib")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn compiled_lib<P>(&mut self, compiled_lib: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let compiled_lib = compiled_lib.into() as PathBuf;
+        if !self.compiled_libs.contains(&compiled_lib) {
+            self.compiled_libs.push(compiled_lib);
+        }
+
+        self
+    }
+
+    /// Marks one or more files as a compiled library.
+    ///
+    /// Equivalent to calling c3c with `-l <lib1> [-l <lib2> ... -l <libN>]`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .compiled_libs(["somelib", "otherlib"])
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn compiled_libs<P>(&mut self, compiled_libs: P) -> &mut Self
+    where
+        P: IntoIterator,
+        P::Item: Into<PathBuf>,
+    {
+        for compiled_lib in compiled_libs {
+            self.compiled_lib(compiled_lib.into());
+        }
+
+        self
+    }
+
+    /// Whether every compiled library is looked up in the compiled library directories before invoking c3c, failing with
+    /// [C3Error::InvalidConfiguration] naming the ones that could not be found.
+    ///
+    /// Default: false.
+    ///
+    /// Libraries are looked for under the names the target's linker would use, e.g. `libfoo.so` and `libfoo.a` for `foo`
+    /// on Linux, or `foo.lib` on Windows. A `:` prefix, as in `:libfoo.a`, looks for the exact file name. Only the
+    /// directories registered with [C3FFI::compiled_lib_dir] are searched, so this cannot be used together with system
+    /// libraries that are found through the linker's default search path.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .compiled_lib_dir("extern/lib")
+    ///     .compiled_lib("somelib")
+    ///     .check_libs_exist(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn check_libs_exist(&mut self, check_libs_exist: bool) -> &mut Self {
+        self.check_libs_exist = check_libs_exist;
+        self
+    }
+
+    /// Marks a directory as containing C3 libraries.
+    ///
+    /// Equivalent to calling c3c with `--libdir <dir>`. The build script re-runs whenever anything in the directory
+    /// changes, e.g. when a vendored library is edited.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .c3_lib_dir("libs")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn c3_lib_dir<P>(&mut self, c3_lib_dir: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let c3_lib_dir = c3_lib_dir.into() as PathBuf;
+        if !self.c3_lib_dirs.contains(&c3_lib_dir) {
+            println!("cargo::rerun-if-changed={}", c3_lib_dir.display());
+            self.c3_lib_dirs.push(c3_lib_dir);
+        }
+
+        self
+    }
+
+    /// Marks one or more directories as containing C3 libraries.
+    ///
+    /// Equivalent to calling c3c with `--libdir <dir1> [--libdir <dir2> ... --libdir <dirN>]`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .c3_lib_dirs(["libs", &format("{}/.local.libs", env!("HOME"))])
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn c3_lib_dirs<P>(&mut self, c3_lib_dirs: P) -> &mut Self
+    where
+        P: IntoIterator,
+        P::Item: Into<PathBuf>,
+    {
+        for c3_lib_dir in c3_lib_dirs {
+            self.c3_lib_dir(c3_lib_dir.into());
+        }
+
+        self
+    }
+
+    /// Marks a directory as containing C3 libraries, searching it before every directory added so far.
+    ///
+    /// Equivalent to calling c3c with `--libdir <dir>` before any other `--libdir`. Useful when several directories
+    /// contain a library with the same name.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .c3_lib_dir("libs")
+    ///     .prepend_c3_lib_dir("patched-libs")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn prepend_c3_lib_dir<P>(&mut self, c3_lib_dir: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let c3_lib_dir = c3_lib_dir.into() as PathBuf;
+        if !self.c3_lib_dirs.contains(&c3_lib_dir) {
+            println!("cargo::rerun-if-changed={}", c3_lib_dir.display());
+        }
+        self.c3_lib_dirs.retain(|existing| *existing != c3_lib_dir);
+        self.c3_lib_dirs.insert(0, c3_lib_dir);
+
+        self
+    }
+
+    /// Marks a file as a C3 library.
+    ///
+    /// Equivalent to calling c3c with `--lib <lib>`. When `lib` is a path to an existing library rather than a name
+    /// looked up in the C3 library directories, the build script re-runs whenever it changes.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .c3_lib("somelib")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn c3_lib<P>(&mut self, c3_lib: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let c3_lib = c3_lib.into() as PathBuf;
+        if !self.c3_libs.contains(&c3_lib) {
+            if c3_lib.exists() {
+                println!("cargo::rerun-if-changed={}", c3_lib.display());
+            }
+            self.c3_libs.push(c3_lib);
+        }
+
+        self
+    }
+
+    /// Marks one or more files as a C3 library.
+    ///
+    /// Equivalent to calling c3c with `--lib <lib1> [--lib <lib2> ... --lib <libN>]`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .c3_libs(["somelib", "otherlib"])
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn c3_libs<P>(&mut self, c3_libs: P) -> &mut Self
+    where
+        P: IntoIterator,
+        P::Item: Into<PathBuf>,
+    {
+        for c3_lib in c3_libs {
+            self.c3_lib(c3_lib.into());
+        }
+
+        self
+    }
+
+    /// Splits the static library into `count` smaller ones, named `lib<name>_0` to `lib<name>_<count - 1>`, for libraries
+    /// large enough to run into linker limits.
+    ///
+    /// The source files are divided into `count` groups of nearly equal size, or one group per file when there are
+    /// fewer files, in the order they were added. Each group is built into its own library which cargo is told to link.
+    ///
+    /// c3c only sees the files of one group at a time, so a module may only `import` modules from its own group, and
+    /// compilation fails otherwise; keep files importing each other next to each other. Calls through `extern`
+    /// declarations across groups still resolve once all the libraries are linked together.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .split_output(2)
+    ///     .files(["extern/thingmabob.c3", "extern/thingmajane.c3", "extern/thingmajig.c3"])
+    ///     .compile("thing");
+    /// ```
+    pub fn split_output(&mut self, count: usize) -> &mut Self {
+        self.split_output = Some(count);
+        self
+    }
+
+    /// Whether `OUT_DIR` is created before invoking c3c if it does not exist yet.
+    ///
+    /// Default: true.
+    ///
+    /// When `false`, a missing `OUT_DIR` fails compilation with [C3Error::InvalidConfiguration] instead.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .create_output_dir(false)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn create_output_dir(&mut self, create_output_dir: bool) -> &mut Self {
+        self.create_output_dir = create_output_dir;
+        self
+    }
+
+    /// Whether the library's file name should include a hash of the build's inputs.
+    ///
+    /// Default: false.
+    ///
+    /// When `true`, the library is written as `lib<name>-<hash>` and then copied to the canonical `lib<name>`, so
+    /// caches and artifact stores can keep differently configured builds apart while cargo still links the same name.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .content_addressed(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn content_addressed(&mut self, content_addressed: bool) -> &mut Self {
+        self.content_addressed = content_addressed;
+        self
+    }
+
+    /// Whether c3c should be skipped when nothing changed since the last successful build of the library.
+    ///
+    /// Default: false.
+    ///
+    /// When `true`, a hash of the build's inputs, i.e. the source files' contents, features, arguments, optimization
+    /// level, target and the other settings affecting the library, is stored in `<name>.c3ne-cache` inside `OUT_DIR`,
+    /// along with the headers c3c generated. When it matches on the next run and the library still exists, c3c is not
+    /// invoked, while the link directives are emitted and [CompileResult::headers] is filled in as usual. Ignored with
+    /// [C3FFI::check_only].
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .cache(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn cache(&mut self, cache: bool) -> &mut Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Overrides how an architecture from a Rust target triple is translated into c3c's naming, for architectures
+    /// [rust_target_to_c3] does not know about or translates differently from what a c3c version expects.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .override_arch("riscv64gc", "riscv64")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn override_arch(&mut self, rust_arch: &str, c3_arch: &str) -> &mut Self {
+        match self
+            .architecture_overrides
+            .iter_mut()
+            .find(|(existing_rust_arch, _)| existing_rust_arch == rust_arch)
+        {
+            Some(existing) => existing.1 = c3_arch.to_string(),
+            None => self
+                .architecture_overrides
+                .push((rust_arch.to_string(), c3_arch.to_string())),
+        }
+
+        self
+    }
+
+    /// Sets the c3c target directly, skipping the translation of cargo's `TARGET` with [rust_target_to_c3] and any
+    /// [C3FFI::override_arch] entirely.
+    ///
+    /// Equivalent to calling c3c with `--target <target>`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .c3_target("linux-aarch64")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn c3_target(&mut self, target: &str) -> &mut Self {
+        self.c3_target = Some(target.to_string());
+        self
+    }
+
+    /// Whether to check that c3c supports the target before building, failing with [C3Error::TargetNotInstalled], which
+    /// lists the targets it does support, instead of c3c's own error.
+    ///
+    /// Default: false.
+    ///
+    /// The targets are read from `c3c --list-targets`, costing an extra c3c invocation per compilation.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .verify_target(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn verify_target(&mut self, verify_target: bool) -> &mut Self {
+        self.verify_target = verify_target;
+        self
+    }
+
+    /// Overrides the file extension c3c is expected to give a library for the given OS and linking mode.
+    ///
+    /// Only needed for unusual platforms where [lib_extension_for] guesses wrong.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .override_extension("linux", LinkingMode::Dynamic, "so.1")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn override_extension(&mut self, os: &str, mode: LinkingMode, ext: &str) -> &mut Self {
+        let ext = ext.trim_start_matches('.').to_string();
+        match self
+            .extension_overrides
+            .iter_mut()
+            .find(|(existing_os, existing_mode, _)| existing_os == os && *existing_mode == mode)
+        {
+            Some(existing) => existing.2 = ext,
+            None => self.extension_overrides.push((os.to_string(), mode, ext)),
+        }
+
+        self
+    }
+
+    /// Whether c3c should be run in a way that makes its output as reproducible as possible.
+    ///
+    /// Default: false.
+    ///
+    /// When `true`, `SOURCE_DATE_EPOCH` (inherited from the environment, `0` otherwise) and `ZERO_AR_DATE=1` are set for
+    /// c3c, source files inside the crate are passed relative to `CARGO_MANIFEST_DIR`, and c3c's temporary build files are
+    /// kept in a fixed directory inside `OUT_DIR` with `--build-dir`.
+    ///
+    /// This cannot control the absolute path of `OUT_DIR` itself, which still ends up in the debug information, nor any
+    /// differences between c3c versions. Combine it with `debug_info(false)` for byte-identical libraries across machines.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .reproducible(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn reproducible(&mut self, reproducible: bool) -> &mut Self {
+        self.reproducible = reproducible;
+        self
+    }
+
+    /// The directory c3c keeps its temporary build files in, relative to `OUT_DIR` unless absolute, so they neither
+    /// clutter `OUT_DIR` nor end up wherever c3c puts them by default.
+    ///
+    /// Default: unset, which means `build` with [C3FFI::reproducible] and c3c's own choice otherwise.
+    ///
+    /// Equivalent to calling c3c with `--build-dir <dir>`. Object files are always written to `obj/<name>` inside
+    /// `OUT_DIR` regardless, so they can be returned in [CompileResult::objects].
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .intermediates_dir("c3-intermediates")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn intermediates_dir<P>(&mut self, intermediates_dir: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let intermediates_dir = intermediates_dir.into() as PathBuf;
+        self.intermediates_dir = Some(intermediates_dir);
+        self
+    }
+
+    /// Pins the ordering of c3c's code generation to the given seed.
+    ///
+    /// c3c has no randomized code generation, and therefore no seed to pass, so its output is already fully determined by
+    /// its inputs. This instead turns on [C3FFI::reproducible], which removes the remaining sources of variation, and
+    /// mixes the seed into the hash used by [C3FFI::content_addressed], so libraries built with different seeds never
+    /// share a name.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .codegen_seed(42)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn codegen_seed(&mut self, seed: u64) -> &mut Self {
+        self.codegen_seed = Some(seed);
+        self.reproducible = true;
+        self
+    }
+
+    /// Only compiles the library when the given cargo feature of the crate being built is enabled.
+    ///
+    /// When the feature is disabled, [C3FFI::compile] does nothing and [C3FFI::attempt_compilation] returns a
+    /// [CompileResult] with [CompileResult::skipped] set. The configuration is not validated either, so source files
+    /// that only exist alongside the feature don't fail the build.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .only_if_feature("c3-accel")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn only_if_feature(&mut self, name: &str) -> &mut Self {
+        let variable = format!("CARGO_FEATURE_{}", name.to_uppercase().replace('-', "_"));
+        println!("cargo::rerun-if-env-changed={}", variable);
+        self.required_feature = Some(variable);
+        self
+    }
+
+    /// Whether the source files should only be checked, without producing a library or emitting any link directives.
+    ///
+    /// Default: false.
+    ///
+    /// When `true`, this is equivalent to calling c3c with `compile-only -C` instead of the command corresponding to the
+    /// linking mode.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .check_only(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn check_only(&mut self, check_only: bool) -> &mut Self {
+        self.check_only = check_only;
+        self
+    }
+
+    /// Overrides the c3c command used to build the library, for c3c versions whose commands differ from the ones this
+    /// crate expects.
+    ///
+    /// Default: `static-lib` or `dynamic-lib` depending on the [LinkingMode], `compile` for WebAssembly.
+    ///
+    /// Commands that a dedicated method already selects, such as `dynamic-lib` for [C3FFI::linking_mode], emit a cargo
+    /// warning suggesting that method.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .subcommand("staticlib")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn subcommand(&mut self, subcommand: &str) -> &mut Self {
+        self.subcommand = Some(subcommand.to_string());
+        self
+    }
+
+    /// Overrides the c3c command used to build the library in the given linking mode only, for c3c versions that renamed
+    /// it, while the other mode keeps its default.
+    ///
+    /// Default: `static-lib` for [LinkingMode::Static] and `dynamic-lib` for [LinkingMode::Dynamic].
+    ///
+    /// Unlike [C3FFI::subcommand], which replaces the command regardless of the linking mode, this keeps
+    /// [C3FFI::compile_bundle] working, since it builds the library in both modes. [C3FFI::subcommand] takes precedence
+    /// when both are set.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .subcommand_for(LinkingMode::Static, "static-library")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn subcommand_for(&mut self, linking_mode: LinkingMode, subcommand: &str) -> &mut Self {
+        match self
+            .subcommand_overrides
+            .iter_mut()
+            .find(|(existing_linking_mode, _)| *existing_linking_mode == linking_mode)
+        {
+            Some((_, existing_subcommand)) => *existing_subcommand = subcommand.to_string(),
+            None => self.subcommand_overrides.push((linking_mode, subcommand.to_string())),
+        }
+        self
+    }
+
+    /// Overrides the c3c command used by [C3FFI::check_only], for c3c versions whose commands differ from the ones this
+    /// crate expects.
+    ///
+    /// Default: `compile-only`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .check_only(true)
+    ///     .check_subcommand("compile-only")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn check_subcommand(&mut self, check_subcommand: &str) -> &mut Self {
+        self.check_subcommand = Some(check_subcommand.to_string());
+        self
+    }
+
+    /// Whether the warnings c3c reports should be re-emitted as cargo warnings.
+    ///
+    /// Default: false.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .forward_warnings(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn forward_warnings(&mut self, forward_warnings: bool) -> &mut Self {
+        self.forward_warnings = forward_warnings;
+        self
+    }
+
+    /// The maximum amount of warnings re-emitted by [C3FFI::forward_warnings], the rest being summarized in a final
+    /// `... and N more` warning.
+    ///
+    /// Default: unlimited.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .forward_warnings(true)
+    ///     .max_forwarded_warnings(20)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn max_forwarded_warnings(&mut self, max_forwarded_warnings: usize) -> &mut Self {
+        self.max_forwarded_warnings = Some(max_forwarded_warnings);
+        self
+    }
+
+    /// Replaces how lines printed by c3c are recognized as errors, warnings or notes, for c3c versions whose diagnostics
+    /// are formatted differently from what this crate expects.
+    ///
+    /// The classification decides what [C3FFI::forward_warnings] forwards and what [CompileResult::warnings] counts. By
+    /// default, lines containing `Error:`, `Warning:` or `Note:` are treated as the corresponding diagnostic.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .diagnostic_classifier(|line| {
+    ///         if line.starts_with("warning") {
+    ///             DiagnosticSeverity::Warning
+    ///         } else if line.starts_with("error") {
+    ///             DiagnosticSeverity::Error
+    ///         } else {
+    ///             DiagnosticSeverity::None
+    ///         }
+    ///     })
+    ///     .forward_warnings(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn diagnostic_classifier<F>(&mut self, diagnostic_classifier: F) -> &mut Self
+    where
+        F: Fn(&str) -> DiagnosticSeverity + 'static,
+    {
+        self.diagnostic_classifier = Some(Box::new(diagnostic_classifier));
+        self
+    }
+
+    /// How a failed compilation is rendered, both by the panic of [C3FFI::compile] and by the [Display] implementation
+    /// of the returned [C3Error::CompilationFailed].
+    ///
+    /// Default: [ErrorFormat::Plain].
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .error_format(c3ne::ErrorFormat::GithubActions)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn error_format(&mut self, error_format: ErrorFormat) -> &mut Self {
+        self.error_format = error_format;
+        self
+    }
+
+    /// Writes the errors and warnings c3c reports to the given file as a JSON array after every compilation, whether it
+    /// succeeded or not, for CI dashboards and other tools.
+    ///
+    /// Each entry has the shape used by [ErrorFormat::Json], i.e. `severity`, `file`, `line`, `column` and `message`,
+    /// the location being `null` when c3c did not report one.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .diagnostics_json(format!("{}/diagnostics.json", std::env::var("OUT_DIR").unwrap()))
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn diagnostics_json<P>(&mut self, diagnostics_json: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        // At the time of writing, rust-analyzer failed to provide suggestions if not explicitly cast.
+        let diagnostics_json = diagnostics_json.into() as PathBuf;
+        self.diagnostics_json = Some(diagnostics_json);
+        self
+    }
+
+    /// Writes Rust `extern "C"` declarations for the compiled library to the given file.
+    ///
+    /// The declarations are translated from the C headers c3c generates alongside the library, ignoring other headers
+    /// in `OUT_DIR`, so this does not work together with `--no-headers` or WebAssembly targets. Only functions whose
+    /// parameters and return type are primitives, or pointers to primitives, are translated; anything else is left out
+    /// with a comment naming the skipped declaration.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .generate_rust_bindings(format!("{}/thing.rs", std::env::var("OUT_DIR").unwrap()))
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn generate_rust_bindings<P>(&mut self, out: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.rust_bindings = Some(out.into());
+        self
+    }
+
+    /// Whether the library is optional, in which case failing to compile it is not an error.
+    ///
+    /// Default: false.
+    ///
+    /// When `true` and compilation fails, including when the configuration is invalid, e.g. a source file is missing or
+    /// the compiler cannot be found, the error is emitted as a cargo warning, `cargo::rustc-cfg=c3_unavailable` is
+    /// set so the crate can fall back to pure Rust with `#[cfg(c3_unavailable)]`, and [C3FFI::attempt_compilation]
+    /// returns a [CompileResult] with [CompileResult::skipped] set.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .optional(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn optional(&mut self, optional: bool) -> &mut Self {
+        self.optional = optional;
+        self
+    }
+
+    /// Sets a cfg for the crate being built once the library has been compiled successfully.
+    ///
+    /// Equivalent to a build script printing `cargo::rustc-cfg=<name>` after compiling, so the crate can check for the
+    /// library with `#[cfg(<name>)]`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .optional(true)
+    ///     .emit_cfg_on_success("c3_available")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn emit_cfg_on_success(&mut self, name: &str) -> &mut Self {
+        if !self.success_cfgs.contains(&name.to_string()) {
+            self.success_cfgs.push(name.to_string());
+        }
+        self
+    }
+
+    /// Declares the `links` key of the package, exporting the library's location to dependent build scripts.
+    ///
+    /// After a successful build, `cargo::metadata=include=<OUT_DIR>` and `cargo::metadata=lib=<library>` are emitted,
+    /// which dependents read as `DEP_<KEY>_INCLUDE` and `DEP_<KEY>_LIB`. Compilation fails with
+    /// [C3Error::InvalidConfiguration] if the package declares a different `links` key. If it declares none, cargo would
+    /// reject the metadata, so a cargo warning is emitted instead. A warning is also emitted when the library's name
+    /// differs from the key, since cargo expects a package with a `links` key to link exactly that library.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .links_key("thing")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn links_key(&mut self, key: &str) -> &mut Self {
+        self.links_key = Some(key.to_string());
+        self
+    }
+
+    /// Writes a JSON manifest describing exactly what was built to the given file, for auditing purposes.
+    ///
+    /// The manifest records the compiler and its version, the c3c target, every argument c3c was called with, and the
+    /// path and hash of each source file and of the produced library. Hashes are 64-bit FNV-1a, which identifies inputs
+    /// reliably but is not a cryptographic guarantee against tampering.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .write_build_manifest(format!("{}/thing-manifest.json", std::env::var("OUT_DIR").unwrap()))
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn write_build_manifest<P>(&mut self, path: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.build_manifest = Some(path.into());
+        self
+    }
+
+    /// Writes the c3c invocation to the given file as a shell script, so it can be reproduced outside of cargo, e.g. for
+    /// bug reports.
+    ///
+    /// The script changes to the project directory if one is set, and runs the compiler with the exact arguments and the
+    /// environment variables set on the builder. Values of environment variables can be hidden with
+    /// [C3FFI::mask_dumped_environment]. When c3c is run several times, e.g. by [C3FFI::compile_many], the script runs
+    /// every invocation in turn.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .dump_command_to(format!("{}/c3c.sh", std::env::var("OUT_DIR").unwrap()))
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn dump_command_to<P>(&mut self, path: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.command_dump = Some(path.into());
+        self
+    }
+
+    /// Whether the values of environment variables are replaced with `***` in the script written by
+    /// [C3FFI::dump_command_to], so secrets don't end up in bug reports.
+    ///
+    /// Default: false.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .dump_command_to("c3c.sh")
+    ///     .mask_dumped_environment(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn mask_dumped_environment(&mut self, mask_dumped_environment: bool) -> &mut Self {
+        self.mask_dumped_environment = mask_dumped_environment;
+        self
+    }
+
+    /// Whether to link the produced library into a throwaway shared library right after building it, failing with
+    /// [C3Error::UndefinedSymbols] if it references symbols nothing provides.
+    ///
+    /// Default: false.
+    ///
+    /// The check uses the C compiler from the `CC` environment variable, or `cc`, and links against the same libraries as
+    /// the C3 library itself. It is skipped on Windows and WebAssembly targets, and when cross-compiling, since the host
+    /// linker cannot link for another target.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .verify_link(true)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn verify_link(&mut self, verify_link: bool) -> &mut Self {
+        self.verify_link = verify_link;
+        self
+    }
+
+    /// Loads the produced library right after building it and checks that it exports the given symbol, failing with
+    /// [C3Error::SmokeTestFailed] otherwise. Can be called several times to check more than one symbol.
+    ///
+    /// Only available with the `smoke-test` feature. The library can only be loaded into the build script itself, so the
+    /// check is limited to [LinkingMode::Dynamic] and skipped with a cargo warning when cross-compiling. Loading the
+    /// library runs its initializers.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .linking_mode(LinkingMode::Dynamic)
+    ///     .smoke_test("thing_init")
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    #[cfg(feature = "smoke-test")]
+    pub fn smoke_test(&mut self, symbol: &str) -> &mut Self {
+        if !self.smoke_test_symbols.contains(&symbol.to_string()) {
+            self.smoke_test_symbols.push(symbol.to_string());
+        }
+        self
+    }
+
+    /// Limits the address space c3c may use, in bytes, so a runaway compilation fails cleanly instead of exhausting the
+    /// machine's memory.
+    ///
+    /// Only supported when building on Linux, where the limit is applied with `setrlimit(RLIMIT_AS)` right before c3c is
+    /// executed. Other hosts emit a cargo warning and ignore the setting. The limit covers virtual memory, which can be
+    /// considerably larger than what c3c actually uses, so leave generous headroom.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .memory_limit(4 * 1024 * 1024 * 1024)
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn memory_limit(&mut self, bytes: u64) -> &mut Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// The number of threads c3c uses for its backend, where `0` is treated as `1`.
+    ///
+    /// Default: unset, leaving the choice to c3c.
+    ///
+    /// Equivalent to calling c3c with `--threads <n>`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .threads(std::env::var("NUM_JOBS").unwrap().parse().unwrap())
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+
+    /// Uses the number of parallel jobs cargo wants, from `NUM_JOBS`, as the thread count, unless [C3FFI::threads] was
+    /// called.
+    ///
+    /// When `NUM_JOBS` is missing or not a number, c3c picks the thread count itself as usual.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .inherit_cargo_jobs()
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn inherit_cargo_jobs(&mut self) -> &mut Self {
+        self.inherit_cargo_jobs = true;
+        self
+    }
+
+    /// The maximum number of c3c processes [C3FFI::compile_many] runs at once, where `0` is treated as `1`.
+    ///
+    /// Default: the number of parallel jobs cargo wants, from `NUM_JOBS`, or the available parallelism when it is
+    /// missing.
+    ///
+    /// Libraries are built in batches of this size, each batch starting once the previous one has finished. Lower it when
+    /// c3c runs out of memory building many large libraries at once, keeping in mind that every process may itself use
+    /// [C3FFI::threads] threads.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .max_parallel(2)
+    ///     .compile_many(&[
+    ///         ("audio", &["extern/audio.c3".into()]),
+    ///         ("video", &["extern/video.c3".into()]),
+    ///         ("codec", &["extern/codec.c3".into()]),
+    ///     ])
+    ///     .unwrap();
+    /// ```
+    pub fn max_parallel(&mut self, max_parallel: usize) -> &mut Self {
+        self.max_parallel = Some(max_parallel.max(1));
+        self
+    }
+
+    /// Replaces the message of the panic [C3FFI::compile] performs when compilation fails.
+    ///
+    /// The callback receives the error and returns the text to panic with, e.g. to add a hint on how to install c3c.
+    /// Without it, the panic message is the error itself.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .on_error(|err| format!("C3 build failed, is c3c installed? {}", err))
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn on_error<F>(&mut self, on_error: F) -> &mut Self
+    where
+        F: FnOnce(&C3Error) -> String + 'static,
+    {
+        self.on_error = Some(Box::new(on_error));
+        self
+    }
+
+    /// Whether the crate is being built for a different target than the machine building it, according to the `TARGET`
+    /// and `HOST` environment variables cargo provides to build scripts.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// let mut build = c3ne::C3FFI::new();
+    /// build.file("extern/thing.c3");
+    /// if !build.is_cross_compiling().unwrap() {
+    ///     build.file("extern/thing_tests.c3");
+    /// }
+    /// build.compile("thing");
+    /// ```
+    pub fn is_cross_compiling(&self) -> Result<bool, C3Error> {
+        Ok(var("TARGET")? != var("HOST")?)
+    }
+
+    /// Attempts to compile the provided C3 source files, panicking if it fails to do so.
+    ///
+    /// The panic message can be replaced with [C3FFI::on_error].
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .file("extern/thing.c3")
+    ///     .compile("thing");
+    /// ```
+    pub fn compile(&mut self, name: &str) {
+        if let Err(err) = self.attempt_compilation(name) {
+            let message = match self.on_error.take() {
+                Some(on_error) => on_error(&err),
+                None => err.to_string(),
+            };
+            panic!("{}", message);
+        }
+    }
+
+    /// Attempts to compile the provided C3 source files like [C3FFI::attempt_compilation], returning everything c3c
+    /// printed instead of a [CompileResult].
+    ///
+    /// When c3c runs more than once, e.g. with [C3FFI::split_output], the outputs are concatenated and the status is the
+    /// one of the last run. A compilation skipped by [C3FFI::only_if_feature], or served from [C3FFI::cache], returns a
+    /// successful status with empty output.
+    ///
+    /// When [C3FFI::optional] turns a failure into a warning, this returns `Ok` with what the failing c3c run printed
+    /// and its non-zero status, so check [Output::status]. If the build failed before c3c ran, e.g. because a source
+    /// file is missing, there is no output to return, and the result is a successful status with empty output like for
+    /// any other skipped compilation.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// let output = c3ne::C3FFI::new()
+    ///     .file("extern/thing.c3")
+    ///     .compile_with_output("thing")
+    ///     .unwrap();
+    /// for line in String::from_utf8_lossy(&output.stderr).lines() {
+    ///     println!("cargo::warning={}", line);
+    /// }
+    /// ```
+    pub fn compile_with_output(&mut self, name: &str) -> Result<Output, C3Error> {
+        self.attempt_compilation(name)?;
+
+        let mut combined = Output {
+            status: ExitStatus::default(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        for output in self.compiler_outputs.drain(..) {
+            combined.status = output.status;
+            combined.stdout.extend(output.stdout);
+            combined.stderr.extend(output.stderr);
+        }
+        Ok(combined)
+    }
+
+    /// Builds the library both as a static and as a dynamic library, sharing every other setting, without telling cargo
+    /// to link either of them.
+    ///
+    /// Call [CompileBundle::emit_link_directives] on the result to link one of them.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// let bundle = c3ne::C3FFI::new()
+    ///     .file("extern/thing.c3")
+    ///     .compile_bundle("thing")
+    ///     .unwrap();
+    /// bundle.emit_link_directives(LinkingMode::Static);
+    /// ```
+    pub fn compile_bundle(&mut self, name: &str) -> Result<CompileBundle, C3Error> {
+        let linking_mode = self.linking_mode;
+        self.defer_link_directives = true;
+        let bundle = self.compile_bundle_parts(name);
+        self.linking_mode = linking_mode;
+        self.defer_link_directives = false;
+
+        bundle
+    }
+
+    /// Checks the provided C3 source files like [C3FFI::check_only], without producing a library or emitting any link
+    /// directives, returning an error if they do not compile.
+    ///
+    /// Files, features, arguments and every other option apply as they would for [C3FFI::attempt_compilation]. The
+    /// check is compiled under the reserved name `__c3ne_check`, whose objects and intermediate files are removed
+    /// afterwards, so it never touches the outputs of a library.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .file("extern/thing.c3")
+    ///     .feature("FAST_PATH")
+    ///     .check()
+    ///     .unwrap();
+    /// ```
+    pub fn check(&mut self) -> Result<(), C3Error> {
+        let check_only = self.check_only;
+        self.check_only = true;
+        let result = self.attempt_compilation(CHECK_NAME);
+        self.check_only = check_only;
+
+        result.and(self.remove_check_outputs()).map(|_| ())
+    }
+
+    /// Removes what c3c left behind for [C3FFI::check] under its reserved name.
+    fn remove_check_outputs(&self) -> Result<(), C3Error> {
+        let out_dir = self.out_dir()?;
+        for dir in [
+            Path::new(&out_dir).join("obj").join(CHECK_NAME),
+            self.llvm_ir_dir(CHECK_NAME)?,
+            self.asm_dir(CHECK_NAME)?,
+        ] {
+            if dir.exists() {
+                fs::remove_dir_all(dir)?;
+            }
+        }
+        let cache_stamp = Path::new(&out_dir).join(format!("{}.c3ne-cache", CHECK_NAME));
+        if cache_stamp.exists() {
+            fs::remove_file(cache_stamp)?;
+        }
+
+        Ok(())
+    }
+
+    /// Compiles several independent libraries with the same settings like [C3FFI::attempt_compilation], running c3c for
+    /// several of them at once on separate threads, at most [C3FFI::max_parallel] at a time.
+    ///
+    /// Each entry is the name of a library and the source files it is built from. Files added to the builder itself are
+    /// not compiled, while sources added with [C3FFI::source_bytes] only go into the first library, like with
+    /// [C3FFI::split_output]. When any library fails, the others are still built, and every failure is returned
+    /// together in [C3Error::Multiple]. With [C3FFI::optional], every failure is emitted as a cargo warning instead and
+    /// `c3_unavailable` is set. With [C3FFI::split_output], the libraries are built one after the other.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// c3ne::C3FFI::new()
+    ///     .optimization_level(c3ne::OptimizationLevel::O2)
+    ///     .compile_many(&[
+    ///         ("audio", &["extern/audio.c3".into()]),
+    ///         ("video", &["extern/video.c3".into(), "extern/codec.c3".into()]),
+    ///     ])
+    ///     .unwrap();
+    /// ```
+    pub fn compile_many(&mut self, libraries: &[(&str, &[PathBuf])]) -> Result<(), C3Error> {
+        let shared_files = std::mem::take(&mut self.files);
+        let generated_sources = std::mem::take(&mut self.generated_sources);
+        let result = self.compile_many_parts(libraries, &generated_sources);
+        self.files = shared_files;
+        self.generated_sources = generated_sources;
+
+        result
+    }
+
+    /// Compiles the provided C3 source files into a fresh temporary directory instead of `OUT_DIR`, returning a guard that
+    /// deletes the directory when dropped along with the path to the library, so tests can build C3 code outside of a
+    /// build script.
+    ///
+    /// Link directives are not printed. The target is still read from `TARGET` unless [C3FFI::c3_target] is set.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// let (dir, library) = c3ne::C3FFI::new()
+    ///     .c3_target("linux-x64")
+    ///     .file("extern/thing.c3")
+    ///     .compile_to_temp("thing")
+    ///     .unwrap();
+    /// assert!(library.starts_with(dir.path()));
+    /// ```
+    pub fn compile_to_temp(&mut self, name: &str) -> Result<(TempDir, PathBuf), C3Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = TempDir {
+            path: env::temp_dir().join(format!(
+                "c3ne-{}-{}-{}",
+                name,
+                process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            )),
+        };
+        fs::create_dir_all(&dir.path)?;
+
+        let defer_link_directives = self.defer_link_directives;
+        self.out_dir = Some(dir.path.clone());
+        self.defer_link_directives = true;
+        let result = self.attempt_compilation(name);
+        self.out_dir = None;
+        self.defer_link_directives = defer_link_directives;
+
+        Ok((dir, result?.library))
+    }
+
+    /// The command c3c would be run with to build the library with the given name, starting with the compiler itself,
+    /// without running it or creating any files.
+    ///
+    /// [C3FFI::modify_args] is not applied, since it is only called for the actual invocation, and environment
+    /// variables are not included. With [C3FFI::split_output], this is the command for all files at once.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// let command_line = c3ne::C3FFI::new()
+    ///     .file("extern/thing.c3")
+    ///     .command_line("thing")
+    ///     .unwrap();
+    /// println!("cargo::warning={}", command_line.join(" "));
+    /// ```
+    pub fn command_line(&self, name: &str) -> Result<Vec<String>, C3Error> {
+        let c3_target = match &self.c3_target {
+            Some(c3_target) => c3_target.clone(),
+            None => translate_target(&var("TARGET")?, &self.architecture_overrides)?,
+        };
+
+        let mut command_line = vec![self.compiler.clone()];
+        command_line.extend(self.compiler_args(name, &c3_target, &self.out_dir()?)?);
+        Ok(command_line)
+    }
+
+    /// Attempts to compile the provided C3 source files, returning an error if it fails to do so.
+    ///
+    /// On success, the returned [CompileResult] describes what was built.
+    ///
+    /// c3c is waited on without a time limit, as it has no optimization budget either, so a compilation that never
+    /// finishes also keeps the build script from finishing. Bound it from the outside, e.g. with a CI job timeout.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// if let Err(err) = c3ne::C3FFInew()
+    ///     .files(["extern/thingmabob.c3", "extern/thingmajane.c3"])
+    ///     .attempt_compilation("thing")
+    /// {
+    ///     panic!("{}", err);
+    /// }
+    /// ```
+    pub fn attempt_compilation(&mut self, name: &str) -> Result<CompileResult, C3Error> {
+        self.generated_files.clear();
+        self.compiler_outputs.clear();
+        self.dumped_commands.clear();
+        self.link_directives.clear();
+        for success_cfg in &self.success_cfgs {
+            println!("cargo::rustc-check-cfg=cfg({})", success_cfg);
+        }
+        if let Some(required_feature) = &self.required_feature
+            && var(required_feature).is_err()
+        {
+            return Ok(CompileResult {
+                skipped: true,
+                ..Default::default()
+            });
+        }
+
+        let compiled = self.validate().and_then(|_| self.compile_library(name));
+        let result = if !self.optional {
+            compiled?
+        } else {
+            println!("cargo::rustc-check-cfg=cfg(c3_unavailable)");
+            match compiled {
+                Ok(result) => result,
+                Err(err) => {
+                    println!("cargo::warning=failed to compile optional C3 library {}:", name);
+                    for line in err.to_string().lines() {
+                        println!("cargo::warning={}", line);
+                    }
+                    println!("cargo::rustc-cfg=c3_unavailable");
+
+                    return Ok(CompileResult {
+                        skipped: true,
+                        ..Default::default()
+                    });
+                }
+            }
+        };
+
+        if !result.skipped && !self.check_only {
+            for success_cfg in &self.success_cfgs {
+                println!("cargo::rustc-cfg={}", success_cfg);
+            }
+            if let Some(links_key) = &self.links_key {
+                self.export_links_metadata(links_key, name, &result)?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Checks [C3FFI::links_key] against the package's `links` key and exports the library's location.
+    fn export_links_metadata(&self, links_key: &str, name: &str, result: &CompileResult) -> Result<(), C3Error> {
+        let declared = match var("CARGO_MANIFEST_LINKS") {
+            Ok(declared) => declared,
+            Err(_) => {
+                println!(
+                    "cargo::warning=links_key(\"{}\") requires `links = \"{}\"` in Cargo.toml, not exporting metadata",
+                    links_key, links_key
+                );
+                return Ok(());
+            }
+        };
+        if declared != links_key {
+            return Err(C3Error::InvalidConfiguration(format!(
+                "links_key is {} but the package declares links = \"{}\"",
+                links_key, declared
+            )));
+        }
+        if name != links_key {
+            println!(
+                "cargo::warning=library {} is linked by a package whose links key is {}",
+                name, links_key
+            );
+        }
+
+        println!("cargo::metadata=include={}", self.out_dir()?);
+        println!("cargo::metadata=lib={}", result.library.display());
+        Ok(())
+    }
+
+    /// Does the actual work behind [C3FFI::compile_many], leaving the files and generated sources for it to restore.
+    fn compile_many_parts(
+        &mut self,
+        libraries: &[(&str, &[PathBuf])],
+        generated_sources: &[(String, Vec<u8>)],
+    ) -> Result<(), C3Error> {
+        self.generated_files.clear();
+        self.compiler_outputs.clear();
+        self.dumped_commands.clear();
+        self.link_directives.clear();
+        for success_cfg in &self.success_cfgs {
+            println!("cargo::rustc-check-cfg=cfg({})", success_cfg);
+        }
+        if let Some(required_feature) = &self.required_feature
+            && var(required_feature).is_err()
+        {
+            return Ok(());
+        }
+
+        let mut errors: Vec<(String, C3Error)> = Vec::new();
+        if self.split_output.is_some() && !self.check_only {
+            for (index, (name, files)) in libraries.iter().enumerate() {
+                self.files = files.to_vec();
+                self.use_generated_sources_for(index, generated_sources);
+                if let Err(err) = self.validate().and_then(|_| self.compile_library(name)) {
+                    errors.push((name.to_string(), err));
+                }
+            }
+        } else {
+            let mut pending_compilations = Vec::new();
+            for (index, (name, files)) in libraries.iter().enumerate() {
+                self.files = files.to_vec();
+                self.use_generated_sources_for(index, generated_sources);
+                match self.validate().and_then(|_| self.prepare_compilation(name)) {
+                    Ok(pending) => pending_compilations.push(pending),
+                    Err(err) => errors.push((name.to_string(), err)),
+                }
+            }
+
+            let max_parallel = self.max_parallel.unwrap_or_else(|| {
+                var("NUM_JOBS")
+                    .ok()
+                    .and_then(|jobs| jobs.parse::<usize>().ok())
+                    .or_else(|| thread::available_parallelism().ok().map(usize::from))
+                    .unwrap_or(1)
+                    .max(1)
+            });
+            let mut outputs: Vec<io::Result<Output>> = Vec::new();
+            for batch in pending_compilations.chunks_mut(max_parallel) {
+                thread::scope(|scope| {
+                    let handles: Vec<_> = batch
+                        .iter_mut()
+                        .map(|pending| scope.spawn(move || pending.run()))
+                        .collect();
+                    outputs.extend(handles.into_iter().map(|handle| handle.join().unwrap()));
+                });
+            }
+            for (pending, output) in pending_compilations.into_iter().zip(outputs) {
+                let name = pending.name.clone();
+                if let Err(err) = self.finish_compilation(pending, output) {
+                    errors.push((name, err));
+                }
+            }
+        }
+
+        if self.optional {
+            println!("cargo::rustc-check-cfg=cfg(c3_unavailable)");
+            for (name, err) in &errors {
+                println!("cargo::warning=failed to compile optional C3 library {}:", name);
+                for line in err.to_string().lines() {
+                    println!("cargo::warning={}", line);
+                }
+            }
+            if !errors.is_empty() {
+                println!("cargo::rustc-cfg=c3_unavailable");
+                return Ok(());
+            }
+        }
+
+        match errors.len() {
+            0 => {
+                for success_cfg in &self.success_cfgs {
+                    println!("cargo::rustc-cfg={}", success_cfg);
+                }
+                Ok(())
+            }
+            1 => Err(errors.pop().unwrap().1),
+            _ => Err(C3Error::Multiple(errors)),
+        }
+    }
+
+    /// Generated sources only go into the first library of [C3FFI::compile_many], so their symbols aren't defined more
+    /// than once.
+    fn use_generated_sources_for(&mut self, index: usize, generated_sources: &[(String, Vec<u8>)]) {
+        if index == 0 {
+            self.generated_sources = generated_sources.to_vec();
+        } else {
+            self.generated_sources.clear();
+        }
+    }
+
+    /// Does the actual work behind [C3FFI::compile_bundle], leaving the linking mode for it to restore.
+    fn compile_bundle_parts(&mut self, name: &str) -> Result<CompileBundle, C3Error> {
+        self.linking_mode = LinkingMode::Static;
+        let static_library = self.attempt_compilation(name)?;
+        let static_link_directives = std::mem::take(&mut self.link_directives);
+        let mut generated_files = std::mem::take(&mut self.generated_files);
+
+        self.linking_mode = LinkingMode::Dynamic;
+        let dynamic_library = self.attempt_compilation(name);
+        generated_files.append(&mut self.generated_files);
+        self.generated_files = generated_files;
+
+        Ok(CompileBundle {
+            static_library,
+            dynamic_library: dynamic_library?,
+            static_link_directives,
+            dynamic_link_directives: std::mem::take(&mut self.link_directives),
+        })
+    }
+
+    /// Rejects malformed feature names and warns about arguments and subcommands that a dedicated method should set
+    /// instead.
+    fn validate(&self) -> Result<(), C3Error> {
+        for feature in &self.features {
+            if feature.is_empty() || feature.starts_with('-') || feature.contains(char::is_whitespace) {
+                return Err(C3Error::InvalidConfiguration(format!(
+                    "feature {:?} must be a bare name, without whitespace or leading dashes",
+                    feature
+                )));
+            }
+        }
+
+        if let Some(file) = self.files.iter().find(|file| !file.exists()) {
+            return Err(C3Error::MissingSourceFile(file.clone()));
+        }
+
+        for (a, b) in &self.conflicting_features {
+            if self.features.contains(a) && self.features.contains(b) {
+                return Err(C3Error::ConflictingFeatures(a.clone(), b.clone()));
+            }
+        }
+
+        for arg in &self.args {
+            if let Some(method) = typed_alternative(arg) {
+                println!("cargo::warning=use {}() instead of arg(\"{}\")", method, arg);
+            }
+        }
+        for (name, subcommand) in [("subcommand", &self.subcommand), ("check_subcommand", &self.check_subcommand)] {
+            if let Some(subcommand) = subcommand
+                && let Some(method) = typed_alternative(subcommand)
+            {
+                println!("cargo::warning=use {}() instead of {}(\"{}\")", method, name, subcommand);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Does the actual work behind [C3FFI::attempt_compilation].
+    fn compile_library(&mut self, name: &str) -> Result<CompileResult, C3Error> {
+        if let Some(split_output) = self.split_output
+            && !self.check_only
+        {
+            return self.compile_split(name, split_output);
+        }
+
+        let mut pending = self.prepare_compilation(name)?;
+        let output = pending.run();
+        self.finish_compilation(pending, output)
+    }
+
+    /// Does everything [C3FFI::compile_library] needs before running c3c, returning the command to run it with.
+    fn prepare_compilation(&mut self, name: &str) -> Result<PendingCompilation, C3Error> {
+        let started = Instant::now();
+
+        let compiler_path =
+            resolve_program(&self.compiler).ok_or_else(|| C3Error::CompilerNotFound(self.compiler.clone()))?;
+        if self.track_compiler {
+            println!("cargo::rerun-if-changed={}", compiler_path.display());
+        }
+
+        for (program, args) in &self.prebuild_commands {
+            let output = Command::new(program).args(args).args(&self.files).output()?;
+            if !output.status.success() {
+                return Err(C3Error::PrebuildFailed {
+                    program: program.clone(),
+                    code: output.status.code(),
+                    output: format!(
+                        "{}{}",
+                        String::from_utf8_lossy(&output.stdout),
+                        String::from_utf8_lossy(&output.stderr)
+                    ),
+                });
+            }
+        }
+
+        // Outside of a build script, e.g. with compile_to_temp, TARGET is only needed when no c3c target is given.
+        let target = var("TARGET").or_else(|err| self.c3_target.clone().ok_or(err))?;
+        let c3_target = match &self.c3_target {
+            Some(c3_target) => c3_target.clone(),
+            None => translate_target(&target, &self.architecture_overrides)?,
+        };
+        let os = c3_target.split('-').next().unwrap_or_default();
+        let is_wasm = os.starts_with("wasm");
+
+        let out_dir = &self.out_dir()?;
+        if self.create_output_dir {
+            fs::create_dir_all(out_dir)?;
+        } else if !Path::new(out_dir).is_dir() {
+            return Err(C3Error::InvalidConfiguration(format!(
+                "output directory {} does not exist",
+                out_dir
+            )));
+        }
+
+        if self.build_config.is_some() && self.project.is_none() {
+            return Err(C3Error::InvalidConfiguration(
+                "build_config can only be used when building a project".to_string(),
+            ));
+        }
+
+        if self.verify_target {
+            let output = Command::new(&self.compiler)
+                .arg("--list-targets")
+                .envs(self.environment_for(Phase::VersionQuery))
+                .output()?;
+            let available = available_targets(&String::from_utf8_lossy(&output.stdout));
+            if !available.contains(&c3_target) {
+                return Err(C3Error::TargetNotInstalled {
+                    target: c3_target,
+                    available,
+                });
+            }
+        }
+
+        if let Some(required) = self.required_min_version {
+            let version_info = self.compiler_version_info()?;
+            let found = version_field(&version_info, "C3 Compiler Version").unwrap_or_default();
+            if parse_version(found).is_none_or(|found| found < required) {
+                return Err(C3Error::CompilerTooOld {
+                    found: found.to_string(),
+                    required: format!("{}.{}.{}", required.0, required.1, required.2),
+                });
+            }
+        }
+
+        if let Some(required) = &self.required_stdlib_version {
+            let version_info = self.compiler_version_info()?;
+            let found = version_field(&version_info, "Stdlib")
+                .or_else(|| version_field(&version_info, "C3 Compiler Version"))
+                .unwrap_or_default();
+            if found != required && !found.starts_with(&format!("{}.", required)) {
+                return Err(C3Error::StdlibVersionMismatch {
+                    found: found.to_string(),
+                    required: required.clone(),
+                });
+            }
+        }
+
+        if let Some(stack_size) = self.stack_size {
+            if stack_size == 0 {
+                return Err(C3Error::InvalidConfiguration(
+                    "stack size must be greater than zero".to_string(),
+                ));
+            }
+
+            let is_elf = matches!(os, "linux" | "android" | "freebsd" | "netbsd" | "openbsd");
+            if !is_elf || self.linking_mode != LinkingMode::Dynamic {
+                println!(
+                    "cargo::warning=stack_size is only supported for dynamic libraries on ELF targets, ignoring it for {}",
+                    target
+                );
+            }
+        }
+
+        if self.check_libs_exist && !is_wasm {
+            let missing: Vec<String> = self
+                .compiled_libs
+                .iter()
+                .map(|compiled_lib| compiled_lib.display().to_string())
+                .filter(|compiled_lib| {
+                    let file_names: Vec<String> = match compiled_lib.strip_prefix(':') {
+                        Some(file_name) => vec![file_name.to_string()],
+                        None if os == "windows" => vec![format!("{}.lib", compiled_lib)],
+                        None if os == "mingw" => vec![
+                            format!("lib{}.dll.a", compiled_lib),
+                            format!("lib{}.a", compiled_lib),
+                            format!("{}.lib", compiled_lib),
+                        ],
+                        None if matches!(os, "macos" | "ios") => {
+                            vec![format!("lib{}.dylib", compiled_lib), format!("lib{}.a", compiled_lib)]
+                        }
+                        None => vec![format!("lib{}.so", compiled_lib), format!("lib{}.a", compiled_lib)],
+                    };
+                    !self.compiled_lib_dirs.iter().any(|compiled_lib_dir| {
+                        file_names.iter().any(|file_name| compiled_lib_dir.join(file_name).is_file())
+                    })
+                })
+                .collect();
+            if !missing.is_empty() {
+                return Err(C3Error::InvalidConfiguration(format!(
+                    "compiled libraries not found in any compiled_lib_dir: {}",
+                    missing.join(", ")
+                )));
+            }
+        }
+
+        if let Some(libc) = self.libc {
+            let matches_target = match libc {
+                LibcKind::Glibc => os == "linux" && target.contains("-gnu"),
+                LibcKind::Musl => target.contains("-musl"),
+                LibcKind::Newlib => target.contains("newlib") || target.contains("-none"),
+            };
+            if !matches_target {
+                return Err(C3Error::InvalidConfiguration(format!(
+                    "libc {:?} cannot be used with target {}",
+                    libc, target
+                )));
+            }
+        }
+
+        if !self.kept_symbols.is_empty() && !is_wasm && self.linking_mode == LinkingMode::Static {
+            return Err(C3Error::InvalidConfiguration(
+                "keep_symbols cannot be used with static libraries, which c3c does not link".to_string(),
+            ));
+        }
+
+        if let Some(macos_sdk) = &self.macos_sdk
+            && !macos_sdk.is_dir()
+        {
+            return Err(C3Error::InvalidConfiguration(format!(
+                "macOS SDK {} does not exist",
+                macos_sdk.display()
+            )));
+        }
+
+        if let Some(object_format) = self.object_format {
+            let expected = ObjectFormat::of_target(&c3_target);
+            if object_format != expected {
+                return Err(C3Error::InvalidConfiguration(format!(
+                    "object format {:?} cannot be used with target {}, which expects {:?}",
+                    object_format, c3_target, expected
+                )));
+            }
+        }
+
+        let is_msvc = os == "windows";
+        if !is_msvc && (self.windows_sdk.is_some() || self.windows_crt.is_some()) {
+            println!(
+                "cargo::warning=windows_sdk and windows_crt only apply to MSVC targets, ignoring them for {}",
+                target
+            );
+        }
+
+        if let Some(link_sysroot) = &self.link_sysroot {
+            if !link_sysroot.exists() {
+                return Err(C3Error::InvalidConfiguration(format!(
+                    "link sysroot {} does not exist",
+                    link_sysroot.display()
+                )));
+            }
+
+            if (!is_wasm && self.linking_mode == LinkingMode::Static) || os == "windows" {
+                println!("cargo::warning=link_sysroot has no effect for this target and linking mode, ignoring it");
+            }
+        }
+
+        let canonical_name: String = format!("{}{}", if is_wasm { "" } else { "lib" }, name);
+        let out_name = self.out_name(name, &c3_target)?;
+        let obj_dir = Path::new(out_dir).join("obj").join(name);
+        let llvm_ir_dir = self.llvm_ir_dir(name)?;
+        if self.emit_llvm && llvm_ir_dir.exists() {
+            fs::remove_dir_all(&llvm_ir_dir)?;
+        }
+        let asm_dir = self.asm_dir(name)?;
+        if self.emit_asm && asm_dir.exists() {
+            fs::remove_dir_all(&asm_dir)?;
+        }
+        // c3c writes into a staging directory whose contents are only moved into OUT_DIR once it succeeds, so an
+        // interrupted or failed build never leaves a partial library where the link directives point.
+        let staging_dir = Path::new(out_dir).join("staging").join(name);
+        if staging_dir.exists() {
+            fs::remove_dir_all(&staging_dir)?;
+        }
+        fs::create_dir_all(&staging_dir)?;
+        if let Some(intermediates_dir) = &self.intermediates_dir {
+            fs::create_dir_all(Path::new(out_dir).join(intermediates_dir))?;
+        }
+        let mut generated_files: Vec<PathBuf> = Vec::new();
+        for file in &self.files {
+            if let Some(path) = self.staged_source_path(out_dir, file)? {
+                let bytes = fs::read(file)?;
+                if fs::read(&path).ok().as_deref() != Some(bytes.as_slice()) {
+                    fs::create_dir_all(path.parent().unwrap())?;
+                    fs::write(&path, bytes)?;
+                }
+                generated_files.push(path);
+            }
+        }
+        for (source_name, bytes) in &self.generated_sources {
+            let path = Path::new(out_dir).join("c3-sources").join(source_name);
+            if fs::read(&path).ok().as_deref() != Some(bytes.as_slice()) {
+                fs::create_dir_all(path.parent().unwrap())?;
+                fs::write(&path, bytes)?;
+            }
+            generated_files.push(path);
+        }
+        let files = self.source_files(out_dir)?;
+        let mut args = self.compiler_args(name, &c3_target, out_dir)?;
+
+        let mut environment_variables: HashMap<String, String> = HashMap::new();
+        for (key, value) in self.environment_for(Phase::Compile) {
+            environment_variables.insert(key.clone(), value.clone());
+        }
+        if self.reproducible {
+            environment_variables
+                .entry("SOURCE_DATE_EPOCH".to_string())
+                .or_insert_with(|| var("SOURCE_DATE_EPOCH").unwrap_or_else(|_| "0".to_string()));
+            environment_variables
+                .entry("ZERO_AR_DATE".to_string())
+                .or_insert_with(|| "1".to_string());
+        }
+
+        if let Some(args_modifier) = &mut self.args_modifier {
+            args_modifier(&mut args);
+        }
+
+        if let Some(command_dump) = &self.command_dump {
+            let mut script = String::from("#!/bin/sh\n# c3c invocation dumped by c3ne.\nset -e\n");
+            if let Some(project) = &self.project {
+                script.push_str(&format!("cd {}\n", shell_quote(&project.display().to_string())));
+            }
+
+            let mut keys: Vec<&String> = environment_variables.keys().collect();
+            keys.sort();
+            let mut line = String::new();
+            for key in keys {
+                let value = if self.mask_dumped_environment {
+                    "***"
+                } else {
+                    &environment_variables[key]
+                };
+                line.push_str(&format!("{}={} ", key, shell_quote(value)));
+            }
+            line.push_str(&shell_quote(&self.compiler));
+            for arg in &args {
+                line.push(' ');
+                line.push_str(&shell_quote(arg));
+            }
+            self.dumped_commands.push(line);
+            for line in &self.dumped_commands {
+                script.push_str(line);
+                script.push('\n');
+            }
+            fs::write(command_dump, script)?;
+            if !self.generated_files.contains(command_dump) {
+                self.generated_files.push(command_dump.clone());
+            }
+        }
+
+        let extension = self.lib_extension(os).to_string();
+        let is_windows_dll = os == "windows" && self.linking_mode == LinkingMode::Dynamic;
+        let cache_stamp = Path::new(out_dir).join(format!("{}.c3ne-cache", name));
+        let cache_digest = if self.cache && !self.check_only {
+            Some(format!("{:016x}", self.config_hash(name, &c3_target)?))
+        } else {
+            None
+        };
+        let cached = cache_digest.as_ref().is_some_and(|cache_digest| {
+            fs::read_to_string(&cache_stamp).is_ok_and(|stamp| stamp.lines().next() == Some(cache_digest.as_str()))
+                && Path::new(out_dir).join(format!("{}.{}", canonical_name, extension)).exists()
+        });
+
+        let command = (!cached).then(|| {
+            let mut command = Command::new(&self.compiler);
+            command.args(&args).envs(environment_variables);
+            if let Some(project) = &self.project {
+                command.current_dir(project);
+            }
+            if let Some(memory_limit) = self.memory_limit {
+                limit_memory(&mut command, memory_limit);
+            }
+            command
+        });
+
+        Ok(PendingCompilation {
+            name: name.to_string(),
+            started,
+            c3_target,
+            out_dir: out_dir.clone(),
+            canonical_name,
+            out_name,
+            obj_dir,
+            llvm_ir_dir,
+            asm_dir,
+            staging_dir,
+            generated_files,
+            files,
+            args,
+            extension,
+            is_windows_dll,
+            cache_stamp,
+            cache_digest,
+            command,
+        })
+    }
+
+    /// Does everything [C3FFI::compile_library] needs after running c3c, given what it printed.
+    fn finish_compilation(
+        &mut self,
+        pending: PendingCompilation,
+        output: io::Result<Output>,
+    ) -> Result<CompileResult, C3Error> {
+        let cached = pending.command.is_none();
+        let PendingCompilation {
+            name,
+            started,
+            c3_target,
+            out_dir,
+            canonical_name,
+            out_name,
+            obj_dir,
+            llvm_ir_dir,
+            asm_dir,
+            staging_dir,
+            mut generated_files,
+            files,
+            args,
+            extension,
+            is_windows_dll,
+            cache_stamp,
+            cache_digest,
+            ..
+        } = pending;
+        let name = name.as_str();
+        let out_dir = out_dir.as_str();
+        let os = c3_target.split('-').next().unwrap_or_default();
+        let is_wasm = os.starts_with("wasm");
+
+        // Only the headers c3c generated for this library, not whatever else ended up in OUT_DIR.
+        let mut headers = Vec::new();
+        if cached {
+            headers = fs::read_to_string(&cache_stamp)?.lines().skip(1).map(PathBuf::from).collect();
+        }
+        if output.as_ref().is_ok_and(|output| output.status.success()) && !self.check_only && !cached {
+            if self.content_addressed {
+                // Keep the canonical name pointing at the freshly built artifact so the link directives below stay stable.
+                fs::copy(
+                    staging_dir.join(format!("{}.{}", out_name, extension)),
+                    staging_dir.join(format!("{}.{}", canonical_name, extension)),
+                )?;
+                let import_library = staging_dir.join(format!("{}.lib", out_name));
+                if is_windows_dll && import_library.exists() {
+                    fs::copy(import_library, staging_dir.join(format!("{}.lib", canonical_name)))?;
+                }
+            }
+            for entry in fs::read_dir(&staging_dir)? {
+                let entry = entry?;
+                let path = Path::new(out_dir).join(entry.file_name());
+                fs::rename(entry.path(), &path)?;
+                if path.extension().is_some_and(|extension| extension == "h") {
+                    headers.push(path.clone());
+                }
+                generated_files.push(path);
+            }
+            headers.sort();
+        }
+        fs::remove_dir_all(&staging_dir)?;
+        let output = output?;
+        self.compiler_outputs.push(output.clone());
+        if self.verbose {
+            eprint!("{}", String::from_utf8_lossy(&output.stdout));
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if let Some(diagnostics_json) = &self.diagnostics_json {
+            let diagnostics: Vec<String> = stderr
+                .lines()
+                .filter_map(Diagnostic::parse)
+                .map(|diagnostic| diagnostic.to_json())
+                .collect();
+            fs::write(diagnostics_json, format!("[{}]\n", diagnostics.join(", ")))?;
+            generated_files.push(diagnostics_json.clone());
+        }
+        let warnings: Vec<&str> = stderr
+            .lines()
+            .filter(|line| self.classify_diagnostic(line) == DiagnosticSeverity::Warning)
+            .collect();
+        if self.forward_warnings {
+            let max_forwarded_warnings = self.max_forwarded_warnings.unwrap_or(usize::MAX);
+            for warning in warnings.iter().take(max_forwarded_warnings) {
+                println!("cargo::warning={}", warning.trim());
+            }
+            if warnings.len() > max_forwarded_warnings {
+                println!("cargo::warning=... and {} more", warnings.len() - max_forwarded_warnings);
+            }
+        }
+
+        if let Some(cache_digest) = &cache_digest
+            && !cached
+        {
+            if output.status.success() {
+                let mut stamp = cache_digest.clone();
+                for header in &headers {
+                    stamp.push('\n');
+                    stamp.push_str(&header.display().to_string());
+                }
+                fs::write(&cache_stamp, stamp)?;
+            } else if cache_stamp.exists() {
+                fs::remove_file(&cache_stamp)?;
+            }
+        }
+
+        if !output.status.success() {
+            return Err(C3Error::CompilationFailed {
+                code: output.status.code(),
+                stderr: stderr.into_owned(),
+                format: self.error_format,
+            });
+        }
+
+        if self.check_only {
+            self.generated_files.append(&mut generated_files);
+            return Ok(CompileResult {
+                warnings: warnings.len(),
+                duration: started.elapsed(),
+                ..Default::default()
+            });
+        }
+
+        let library = Path::new(out_dir).join(format!("{}.{}", canonical_name, extension));
+        if !self.merged_libs.is_empty() && !cached {
+            if is_wasm || self.linking_mode != LinkingMode::Static {
+                println!("cargo::warning=merge_with only supports static libraries, ignoring it");
+            } else {
+                self.merge_archives(&library, os)?;
+            }
+        }
+
+        if let Some(rust_bindings) = &self.rust_bindings {
+            if headers.is_empty() {
+                return Err(C3Error::InvalidConfiguration(
+                    "generate_rust_bindings requires the C headers c3c generates for libraries".to_string(),
+                ));
+            }
+
+            let mut bindings = String::new();
+            for header in &headers {
+                bindings.push_str(&c_header_to_rust(header, &fs::read_to_string(header)?));
+            }
+            fs::write(rust_bindings, bindings)?;
+            generated_files.push(rust_bindings.clone());
+        }
+
+        if let Some(build_manifest) = &self.build_manifest {
+            let version_info = self.compiler_version_info()?;
+            let mut inputs = Vec::new();
+            for file in &files {
+                inputs.push(format!(
+                    "{{\"path\": {}, \"fnv1a64\": \"{:016x}\"}}",
+                    json_string(&file.display().to_string()),
+                    content_hash(&fs::read(file)?)
+                ));
+            }
+
+            let manifest = [
+                "{".to_string(),
+                format!("  \"compiler\": {},", json_string(&self.compiler)),
+                format!(
+                    "  \"compiler_version\": {},",
+                    json_string(version_field(&version_info, "C3 Compiler Version").unwrap_or_default())
+                ),
+                format!("  \"target\": {},", json_string(&c3_target)),
+                format!(
+                    "  \"args\": [{}],",
+                    args.iter().map(|arg| json_string(arg)).collect::<Vec<_>>().join(", ")
+                ),
+                format!("  \"inputs\": [{}],", inputs.join(", ")),
+                format!(
+                    "  \"output\": {{\"path\": {}, \"fnv1a64\": \"{:016x}\"}}",
+                    json_string(&library.display().to_string()),
+                    content_hash(&fs::read(&library)?)
+                ),
+                "}\n".to_string(),
+            ]
+            .join("\n");
+            fs::write(build_manifest, manifest)?;
+            generated_files.push(build_manifest.clone());
+        }
+
+        if self.verify_link && !is_wasm && os != "windows" && !self.is_cross_compiling()? {
+            generated_files.push(self.link_check(&library, os, out_dir)?);
+        }
+
+        #[cfg(feature = "smoke-test")]
+        if !self.smoke_test_symbols.is_empty() {
+            if self.linking_mode != LinkingMode::Dynamic || is_wasm {
+                println!("cargo::warning=smoke_test only supports dynamic libraries, ignoring it");
+            } else if self.is_cross_compiling()? {
+                println!("cargo::warning=smoke_test cannot load libraries built for another target, skipping it");
+            } else {
+                self.smoke_test_library(&library)?;
+            }
+        }
+
+        // Rust cannot link against a DLL directly, only against the import library c3c produces alongside it.
+        let import_library = Path::new(out_dir).join(format!("{}.lib", canonical_name));
+        let import_library = (is_windows_dll && import_library.exists()).then_some(import_library);
+
+        let mut link_directives: Vec<String> = Vec::new();
+        if !is_wasm {
+            let link_search = if self.relative_link_search {
+                relative_path(Path::new(&var("CARGO_MANIFEST_DIR")?), Path::new(out_dir))
+                    .unwrap_or_else(|| PathBuf::from(out_dir))
+            } else {
+                PathBuf::from(out_dir)
+            };
+            link_directives.push(format!("cargo::rustc-link-search=native={}", link_search.display()));
+            if import_library.is_some() {
+                link_directives.push(format!("cargo::rustc-link-lib=dylib={}", canonical_name));
+            } else {
+                match self.linking_mode {
+                    LinkingMode::Static => link_directives.push(format!("cargo::rustc-link-lib=static={}", name)),
+                    LinkingMode::Dynamic => link_directives.push(format!("cargo::rustc-link-lib=dylib={}", name)),
+                }
+            }
+            if let Some(libc) = self.libc {
+                link_directives.push(format!("cargo::rustc-link-lib={}", libc.link_lib()));
+            }
+            if let Some(sanitizer) = self.sanitizer {
+                link_directives.push(format!("cargo::rustc-link-arg=-fsanitize={}", sanitizer.to_str()));
+            }
+        } else {
+            link_directives.push(format!(
+                "cargo::rustc-link-arg={}",
+                Path::new(out_dir)
+                    .join(canonical_name.clone() + ".wasm")
+                    .as_os_str()
+                    .to_str()
+                    .unwrap()
+            ));
+        }
+        if !self.defer_link_directives {
+            for link_directive in &link_directives {
+                println!("{}", link_directive);
+            }
+        }
+        self.link_directives.extend(link_directives);
+
+        let objects = files_with_extensions(&obj_dir, &["o", "obj"]);
+        self.generated_files.append(&mut generated_files);
+        self.generated_files.extend(objects.iter().cloned());
+        if self.emit_llvm {
+            self.generated_files.extend(files_with_extensions(&llvm_ir_dir, &["ll"]));
+        }
+        if self.emit_asm {
+            self.generated_files.extend(files_with_extensions(&asm_dir, &["s"]));
+        }
+
+        Ok(CompileResult {
+            libraries: vec![library.clone()],
+            library,
+            import_library,
+            objects,
+            headers,
+            warnings: warnings.len(),
+            duration: started.elapsed(),
+            skipped: false,
+        })
+    }
+
+    /// Every argument c3c is run with to build the library with the given name into `out_dir`, excluding the compiler
+    /// itself and before [C3FFI::modify_args] is applied.
+    fn compiler_args(&self, name: &str, c3_target: &str, out_dir: &str) -> Result<Vec<String>, C3Error> {
+        let os = c3_target.split('-').next().unwrap_or_default();
+        let is_wasm = os.starts_with("wasm");
+        let is_msvc = os == "windows";
+
+        let mut extra_linker_arguments: Vec<String> = Vec::new();
+        if let Some(stack_size) = self.stack_size
+            && stack_size > 0
+            && matches!(os, "linux" | "android" | "freebsd" | "netbsd" | "openbsd")
+            && self.linking_mode == LinkingMode::Dynamic
+        {
+            extra_linker_arguments.push(format!("-zstack-size={}", stack_size));
+        }
+        if is_wasm || self.linking_mode == LinkingMode::Dynamic {
+            for symbol in &self.kept_symbols {
+                match os {
+                    _ if is_wasm => extra_linker_arguments.push(format!("--export={}", symbol)),
+                    "macos" | "ios" => {
+                        extra_linker_arguments.push("-u".to_string());
+                        extra_linker_arguments.push(format!("_{}", symbol));
+                    }
+                    "windows" => extra_linker_arguments.push(format!("/INCLUDE:{}", symbol)),
+                    _ => extra_linker_arguments.push(format!("--undefined={}", symbol)),
+                }
+            }
+        }
+        if let Some(link_sysroot) = &self.link_sysroot
+            && (is_wasm || self.linking_mode == LinkingMode::Dynamic)
+            && os != "windows"
+        {
+            if matches!(os, "macos" | "ios") {
+                extra_linker_arguments.push("-syslibroot".to_string());
+                extra_linker_arguments.push(link_sysroot.display().to_string());
+            } else {
+                extra_linker_arguments.push(format!("--sysroot={}", link_sysroot.display()));
+            }
+        }
+
+        let command_corresponding_linking = if self.check_only {
+            self.check_subcommand.as_deref().unwrap_or("compile-only")
+        } else if let Some(subcommand) = &self.subcommand {
+            subcommand
+        } else if self.project.is_some() {
+            "build"
+        } else if is_wasm {
+            "compile"
+        } else if let Some((_, subcommand)) = self
+            .subcommand_overrides
+            .iter()
+            .find(|(linking_mode, _)| *linking_mode == self.linking_mode)
+        {
+            subcommand
+        } else {
+            match self.linking_mode {
+                LinkingMode::Static => "static-lib",
+                LinkingMode::Dynamic => "dynamic-lib",
+            }
+        };
+        let debug_flag = format!("-g{}", if self.debug_info { "" } else { "0" });
+        let optimization_level = self
+            .optimization_level_overrides
+            .iter()
+            .find(|(os_or_arch, _)| c3_target.split('-').any(|part| part == os_or_arch))
+            .map_or(&self.optimization_level, |(_, optimization_level)| optimization_level);
+        let optimization_level_flag = format!("-{}", optimization_level.to_str());
+        let threads = match self.threads {
+            None if self.inherit_cargo_jobs => var("NUM_JOBS").ok().and_then(|jobs| jobs.parse::<usize>().ok()),
+            threads => threads,
+        };
+        let threads = threads.map(|threads| threads.max(1).to_string());
+        let sanitizer_flag = self.sanitizer.as_ref().map(Sanitizer::to_flag);
+        let out_name = self.out_name(name, c3_target)?;
+        let obj_dir = Path::new(out_dir).join("obj").join(name);
+        let obj_dir_str = obj_dir.to_str().unwrap();
+        let build_dir = match &self.intermediates_dir {
+            Some(intermediates_dir) => Some(Path::new(out_dir).join(intermediates_dir)),
+            None if self.reproducible => Some(Path::new(out_dir).join("build")),
+            None => None,
+        };
+        let llvm_ir_dir = self.llvm_ir_dir(name)?;
+        let asm_dir = self.asm_dir(name)?;
+        let staging_dir = Path::new(out_dir).join("staging").join(name);
+        let staging_dir_str = staging_dir.to_str().unwrap();
+        let files = self.source_files(out_dir)?;
+
+        let mut args: Vec<&str> = vec![command_corresponding_linking];
+        if let Some(build_config) = &self.build_config {
+            args.push(build_config);
+        }
+        args.extend([
+            &debug_flag,
+            &optimization_level_flag,
+            "--output-dir",
+            staging_dir_str,
+            "-o",
+            &out_name,
+            "--obj-out",
+            obj_dir_str,
+            "--target",
+            c3_target,
+        ]);
+        if self.check_only {
+            args.push("-C");
+        }
+        if self.link_dead_code {
+            args.push("--no-strip-unused");
+        }
+        if self.emit_llvm {
+            args.push("--emit-llvm");
+            args.push("--llvm-out");
+            args.push(llvm_ir_dir.to_str().unwrap());
+        }
+        if self.emit_asm {
+            args.push("--emit-asm");
+            args.push("--asm-out");
+            args.push(asm_dir.to_str().unwrap());
+        }
+        match self.compiler_verbosity.unwrap_or(self.verbose as u8) {
+            0 => {}
+            1 => args.push("-v"),
+            2 => args.push("-vv"),
+            _ => args.push("-vvv"),
+        }
+        if let Some(threads) = &threads {
+            args.push("--threads");
+            args.push(threads);
+        }
+        match self.single_module {
+            Some(true) => args.push("--single-module=yes"),
+            Some(false) => args.push("--single-module=no"),
+            None => {}
+        }
+        if let Some(reloc_flag) = self.reloc_model.as_ref().and_then(RelocModel::to_flag) {
+            args.push(reloc_flag);
+        }
+        if let Some(sanitizer_flag) = &sanitizer_flag {
+            args.push(sanitizer_flag);
+        }
+        if let Some(libc_flag) = self.libc.as_ref().and_then(LibcKind::to_flag) {
+            args.push(libc_flag);
+        }
+        match self.panic_messages {
+            Some(true) => args.push("--panic-msg=yes"),
+            Some(false) => args.push("--panic-msg=no"),
+            None => {
+                if let Some(panic_strategy_flag) = self.panic_strategy.to_flag() {
+                    args.push(panic_strategy_flag);
+                }
+            }
+        }
+        match self.backtrace {
+            Some(true) => args.push("--show-backtrace=yes"),
+            Some(false) => args.push("--show-backtrace=no"),
+            None => {}
+        }
+        if let Some(macos_sdk) = &self.macos_sdk {
+            args.push("--macossdk");
+            args.push(macos_sdk.to_str().unwrap());
+        }
+        if is_msvc {
+            if let Some(windows_sdk) = &self.windows_sdk {
+                args.push("--winsdk");
+                args.push(windows_sdk.to_str().unwrap());
+            }
+            if let Some(windows_crt) = &self.windows_crt {
+                args.push(windows_crt.to_flag());
+            }
+        }
+
+        for feature in &self.features {
+            args.push("-D");
+            args.push(feature);
+        }
+        for linker_argument in self.linker_arguments.iter().chain(&extra_linker_arguments) {
+            args.push("-z");
+            args.push(linker_argument);
+        }
+        for c3_lib_dir in &self.c3_lib_dirs {
+            args.push("--libdir");
+            args.push(c3_lib_dir.as_os_str().to_str().unwrap());
+        }
+        for c3_lib in &self.c3_libs {
+            args.push("--lib");
+            args.push(c3_lib.as_os_str().to_str().unwrap());
+        }
+
+        if !is_wasm {
+            for compiled_lib_dir in &self.compiled_lib_dirs {
+                args.push("-L");
+                args.push(compiled_lib_dir.as_os_str().to_str().unwrap());
+            }
+            for compiled_lib in &self.compiled_libs {
+                args.push("-l");
+                args.push(compiled_lib.as_os_str().to_str().unwrap());
+            }
+        } else {
+            args.append(&mut vec![
+                "--link-libc=no",
+                "--use-stdlib=yes",
+                "--no-entry",
+                "-z",
+                "--relocatable"
+            ]);
+        }
+
+        if let Some(build_dir) = &build_dir {
+            args.push("--build-dir");
+            args.push(build_dir.to_str().unwrap());
+        }
+
+        if self.project.is_none() {
+            for file in &files {
+                args.push(file.as_os_str().to_str().unwrap());
+            }
+        }
+        for arg in &self.args {
+            args.push(arg);
+        }
+
+        Ok(args.into_iter().map(String::from).collect())
+    }
+
+    /// The file name of the library with the given name, without its extension, which includes the configuration hash
+    /// with [C3FFI::content_addressed].
+    fn out_name(&self, name: &str, c3_target: &str) -> Result<String, C3Error> {
+        let is_wasm = c3_target.starts_with("wasm");
+        let canonical_name: String = format!("{}{}", if is_wasm { "" } else { "lib" }, name);
+        if self.content_addressed {
+            Ok(format!("{}-{:016x}", canonical_name, self.config_hash(name, c3_target)?))
+        } else {
+            Ok(canonical_name)
+        }
+    }
+
+    /// The source files passed to c3c, with those staged for [C3FFI::treat_as_c3] and [C3FFI::source_bytes] pointing at
+    /// their copies in `c3-sources` inside `out_dir`.
+    fn source_files(&self, out_dir: &str) -> Result<Vec<PathBuf>, C3Error> {
+        let manifest_dir = if self.reproducible {
+            Some(PathBuf::from(var("CARGO_MANIFEST_DIR")?))
+        } else {
+            None
+        };
+
+        let mut files: Vec<PathBuf> = Vec::new();
+        for file in &self.files {
+            files.push(match (self.staged_source_path(out_dir, file)?, &manifest_dir) {
+                (Some(path), _) => path,
+                (None, Some(manifest_dir)) => file.strip_prefix(manifest_dir).unwrap_or(file).to_path_buf(),
+                (None, None) => file.clone(),
+            });
+        }
+        for (source_name, _) in &self.generated_sources {
+            files.push(Path::new(out_dir).join("c3-sources").join(source_name));
+        }
+
+        Ok(files)
+    }
+
+    /// Where a file with an extension registered with [C3FFI::treat_as_c3] is copied to with a `.c3` extension, or
+    /// [None] for any other file.
+    fn staged_source_path(&self, out_dir: &str, file: &Path) -> Result<Option<PathBuf>, C3Error> {
+        let has_custom_extension = file
+            .extension()
+            .is_some_and(|ext| self.c3_extensions.iter().any(|c3_extension| ext == c3_extension.as_str()));
+        if !has_custom_extension {
+            return Ok(None);
+        }
+
+        let mut staged_name = manifest_relative_path(file)?.into_os_string();
+        staged_name.push(".c3");
+        Ok(Some(Path::new(out_dir).join("c3-sources").join(staged_name)))
+    }
+
+    /// Builds one library per group of source files for [C3FFI::split_output].
+    fn compile_split(&mut self, name: &str, count: usize) -> Result<CompileResult, C3Error> {
+        if count == 0 {
+            return Err(C3Error::InvalidConfiguration(
+                "split_output needs at least one group".to_string(),
+            ));
+        }
+        if self.linking_mode != LinkingMode::Static {
+            return Err(C3Error::InvalidConfiguration(
+                "split_output can only be used with static libraries".to_string(),
+            ));
+        }
+
+        let started = Instant::now();
+        let files = std::mem::take(&mut self.files);
+        let generated_sources = std::mem::take(&mut self.generated_sources);
+        let groups: Vec<Vec<PathBuf>> = if files.is_empty() {
+            vec![Vec::new()]
+        } else {
+            // The first `len % count` groups get one file more, so every group gets a file when there are enough.
+            let count = count.min(files.len());
+            let (size, larger) = (files.len() / count, files.len() % count);
+            let mut rest = files.as_slice();
+            (0..count)
+                .map(|index| {
+                    let (group, remaining) = rest.split_at(size + usize::from(index < larger));
+                    rest = remaining;
+                    group.to_vec()
+                })
+                .collect()
+        };
+
+        self.split_output = None;
+        let mut combined = CompileResult::default();
+        let mut outcome = Ok(());
+        for (index, group) in groups.into_iter().enumerate() {
+            self.files = group;
+            // Generated sources only go into the first library, so their symbols aren't defined more than once.
+            if index == 0 {
+                self.generated_sources = generated_sources.clone();
+            } else {
+                self.generated_sources.clear();
+            }
+
+            match self.compile_library(&format!("{}_{}", name, index)) {
+                Ok(part) => {
+                    combined.libraries.push(part.library);
+                    combined.objects.extend(part.objects);
+                    for header in part.headers {
+                        if !combined.headers.contains(&header) {
+                            combined.headers.push(header);
+                        }
+                    }
+                    combined.warnings += part.warnings;
+                }
+                Err(err) => {
+                    outcome = Err(err);
+                    break;
+                }
+            }
+        }
+        self.files = files;
+        self.generated_sources = generated_sources;
+        self.split_output = Some(count);
+        outcome?;
+
+        combined.library = combined.libraries[0].clone();
+        combined.duration = started.elapsed();
+        Ok(combined)
+    }
+
+    /// Every file the last compilation created, e.g. the library, its headers and objects, staged sources, and sidecars
+    /// such as the build manifest, so they can be cleaned up selectively.
+    ///
+    /// Files c3c produced in a build directory of its own, like with [C3FFI::reproducible], are not included.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// let mut build = c3ne::C3FFI::new();
+    /// build.file("extern/thing.c3").compile("thing");
+    /// for file in build.generated_files() {
+    ///     println!("{}", file.display());
+    /// }
+    /// ```
+    pub fn generated_files(&self) -> &[PathBuf] {
+        &self.generated_files
+    }
+
+    /// The directory [C3FFI::emit_llvm] writes the LLVM IR of the library with the given name to, which is `llvm/<name>`
+    /// inside `OUT_DIR`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// let mut build = c3ne::C3FFI::new();
+    /// build.emit_llvm(true).file("extern/thing.c3").compile("thing");
+    /// println!("cargo::warning=IR in {}", build.llvm_ir_dir("thing").unwrap().display());
+    /// ```
+    pub fn llvm_ir_dir(&self, name: &str) -> Result<PathBuf, C3Error> {
+        Ok(Path::new(&self.out_dir()?).join("llvm").join(name))
+    }
+
+    /// The directory [C3FFI::emit_asm] writes the assembly of the library with the given name to, which is `asm/<name>`
+    /// inside `OUT_DIR`.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// let mut build = c3ne::C3FFI::new();
+    /// build.emit_asm(true).file("extern/thing.c3").compile("thing");
+    /// println!("cargo::warning=assembly in {}", build.asm_dir("thing").unwrap().display());
+    /// ```
+    pub fn asm_dir(&self, name: &str) -> Result<PathBuf, C3Error> {
+        Ok(Path::new(&self.out_dir()?).join("asm").join(name))
+    }
+
+    /// Maps each registered source file to the object file c3c produced for it, as listed in `result`.
+    ///
+    /// c3c emits one object file per module rather than per source file, so each file is mapped through the first
+    /// `module` declaration it contains. Files whose module has no matching object, e.g. because everything was compiled
+    /// as a single module, are left out.
+    ///
+    ///
+    /// Example:
+    /// ```rs
+    /// let mut build = c3ne::C3FFI::new();
+    /// let result = build
+    ///     .files(["extern/thingmabob.c3", "extern/thingmajane.c3"])
+    ///     .attempt_compilation("thing")
+    ///     .unwrap();
+    /// for (source, object) in build.object_map(&result) {
+    ///     println!("{} -> {}", source.display(), object.display());
+    /// }
+    /// ```
+    pub fn object_map(&self, result: &CompileResult) -> HashMap<PathBuf, PathBuf> {
+        let mut object_map = HashMap::new();
+        for file in &self.files {
+            let Some(module) = fs::read_to_string(file).ok().and_then(|source| c3_module_name(&source)) else {
+                continue;
+            };
+
+            // c3c names object files after the module, with `::` separators replaced by dots.
+            let object_name = module.replace("::", ".");
+            if let Some(object) = result
+                .objects
+                .iter()
+                .find(|object| object.file_stem().and_then(|stem| stem.to_str()) == Some(&object_name))
+            {
+                object_map.insert(file.clone(), object.clone());
+            }
+        }
+
+        object_map
+    }
+
+    /// Classifies a line printed by c3c, taking [C3FFI::diagnostic_classifier] into account.
+    fn classify_diagnostic(&self, line: &str) -> DiagnosticSeverity {
+        if let Some(diagnostic_classifier) = &self.diagnostic_classifier {
+            return diagnostic_classifier(line);
+        }
+
+        if line.contains("Error:") {
+            DiagnosticSeverity::Error
+        } else if line.contains("Warning:") {
+            DiagnosticSeverity::Warning
+        } else if line.contains("Note:") {
+            DiagnosticSeverity::Note
+        } else {
+            DiagnosticSeverity::None
+        }
+    }
+
+    /// The environment variables for the given phase, with phase-scoped ones taking precedence and, with
+    /// [C3FFI::case_insensitive_env], names differing only in case merged into the one set last.
+    fn environment_for(&self, phase: Phase) -> Vec<(&String, &String)> {
+        let ignore_case = self.env_keys_ignore_case();
+        let mut environment_variables: Vec<(&String, &String)> = Vec::new();
+        let phase_environment_variables = self
+            .phase_environment_variables
+            .iter()
+            .filter(|(existing_phase, _, _)| *existing_phase == phase)
+            .map(|(_, key, value)| (key, value));
+        let shared_environment_variables = self.environment_variables.iter().map(|(key, value)| (key, value));
+        for (key, value) in shared_environment_variables.chain(phase_environment_variables) {
+            environment_variables.retain(|(existing_key, _)| !same_env_key(existing_key, key, ignore_case));
+            environment_variables.push((key, value));
+        }
+
+        environment_variables
+    }
+
+    /// Whether environment variable names are compared ignoring case, see [C3FFI::case_insensitive_env].
+    fn env_keys_ignore_case(&self) -> bool {
+        self.case_insensitive_env
+            .unwrap_or_else(|| var("CARGO_CFG_TARGET_OS").is_ok_and(|target_os| target_os == "windows"))
+    }
+
+    /// The directory libraries are built in, `OUT_DIR` unless [C3FFI::compile_to_temp] is building into a temporary one.
+    fn out_dir(&self) -> Result<String, C3Error> {
+        match &self.out_dir {
+            Some(out_dir) => Ok(out_dir.to_string_lossy().into_owned()),
+            None => var("OUT_DIR"),
+        }
+    }
+
+    /// Runs `c3c --version` with the configured environment, returning what it printed.
+    fn compiler_version_info(&self) -> Result<String, C3Error> {
+        let output = Command::new(&self.compiler)
+            .arg("--version")
+            .envs(self.environment_for(Phase::VersionQuery))
+            .output()?;
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Replaces the library with an archive that also contains the libraries given to [C3FFI::merge_with].
+    fn merge_archives(&self, library: &Path, os: &str) -> Result<(), C3Error> {
+        let mut merged_name = library.file_name().unwrap().to_os_string();
+        merged_name.push(".merged");
+        let merged = library.with_file_name(merged_name);
+        if merged.exists() {
+            fs::remove_file(&merged)?;
+        }
+
+        let (program, output) = match os {
+            "macos" | "ios" => {
+                let output = Command::new("libtool")
+                    .arg("-static")
+                    .arg("-o")
+                    .arg(&merged)
+                    .arg(library)
+                    .args(&self.merged_libs)
+                    .output()?;
+                ("libtool".to_string(), output)
+            }
+            "windows" => {
+                let output = Command::new("lib.exe")
+                    .arg("/NOLOGO")
+                    .arg(format!("/OUT:{}", merged.display()))
+                    .arg(library)
+                    .args(&self.merged_libs)
+                    .output()?;
+                ("lib.exe".to_string(), output)
+            }
+            _ => {
+                // GNU ar only merges archives, rather than nesting them, through an MRI script.
+                let mut script = format!("CREATE {}\nADDLIB {}\n", merged.display(), library.display());
+                for lib in &self.merged_libs {
+                    script.push_str(&format!("ADDLIB {}\n", lib.display()));
+                }
+                script.push_str("SAVE\nEND\n");
+
+                let ar = var("AR").unwrap_or_else(|_| "ar".to_string());
+                let mut child = Command::new(&ar)
+                    .arg("-M")
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()?;
+                child.stdin.take().unwrap().write_all(script.as_bytes())?;
+                (ar, child.wait_with_output()?)
+            }
+        };
+        if !output.status.success() {
+            return Err(C3Error::ArchiveMergeFailed {
+                program,
+                code: output.status.code(),
+                output: format!(
+                    "{}{}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                ),
+            });
+        }
+
+        fs::rename(merged, library)?;
+        Ok(())
+    }
+
+    /// Links the library into a throwaway shared library for [C3FFI::verify_link], collecting any undefined symbols.
+    ///
+    /// Returns the path to the throwaway library.
+    fn link_check(&self, library: &Path, os: &str, out_dir: &str) -> Result<PathBuf, C3Error> {
+        let linker = var("CC").unwrap_or_else(|_| "cc".to_string());
+        let output_path = Path::new(out_dir).join("verify-link").join(library.file_name().unwrap());
+        fs::create_dir_all(output_path.parent().unwrap())?;
+
+        let mut command = Command::new(linker);
+        command.envs(self.environment_for(Phase::VerifyLink)).arg("-o").arg(&output_path);
+        if os == "macos" {
+            command.arg("-dynamiclib");
+            match self.linking_mode {
+                LinkingMode::Static => command.arg(format!("-Wl,-force_load,{}", library.display())),
+                LinkingMode::Dynamic => command.arg(library),
+            };
+        } else {
+            command.args(["-shared", "-Wl,--no-undefined", "-Wl,--no-allow-shlib-undefined"]);
+            match self.linking_mode {
+                LinkingMode::Static => command
+                    .arg("-Wl,--whole-archive")
+                    .arg(library)
+                    .arg("-Wl,--no-whole-archive"),
+                LinkingMode::Dynamic => command.arg(library),
+            };
+        }
+        for compiled_lib_dir in &self.compiled_lib_dirs {
+            command.arg("-L").arg(compiled_lib_dir);
+        }
+        for compiled_lib in &self.compiled_libs {
+            command.arg(format!("-l{}", compiled_lib.display()));
+        }
+        let output = command.output()?;
+        if output.status.success() {
+            return Ok(output_path);
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut symbols: Vec<String> = Vec::new();
+        for line in stderr.lines() {
+            // GNU ld: "undefined reference to `symbol'", lld: "undefined symbol: symbol",
+            // ld64: "  "_symbol", referenced from:"
+            let symbol = if let Some((_, rest)) = line.split_once("undefined reference to `") {
+                rest.split('\'').next()
+            } else if let Some((_, rest)) = line.split_once("undefined symbol: ") {
+                Some(rest.trim())
+            } else if line.trim_end().ends_with("referenced from:") {
+                line.trim().split('"').nth(1).map(|symbol| symbol.strip_prefix('_').unwrap_or(symbol))
+            } else {
+                None
+            };
+            if let Some(symbol) = symbol {
+                let symbol = symbol.to_string();
+                if !symbols.contains(&symbol) {
+                    symbols.push(symbol);
+                }
+            }
+        }
+        if symbols.is_empty() {
+            return Err(C3Error::InvalidConfiguration(format!(
+                "verify_link failed to link {}: {}",
+                library.display(),
+                stderr.trim()
+            )));
+        }
+
+        Err(C3Error::UndefinedSymbols(symbols))
+    }
+
+    /// Loads the library for [C3FFI::smoke_test] and looks up every registered symbol.
+    #[cfg(feature = "smoke-test")]
+    fn smoke_test_library(&self, library: &Path) -> Result<(), C3Error> {
+        // SAFETY: the library was just built from the user's sources, running its initializers is the point of the test.
+        let loaded = unsafe { libloading::Library::new(library) }.map_err(|err| C3Error::SmokeTestFailed {
+            symbol: None,
+            reason: err.to_string(),
+        })?;
+        for symbol in &self.smoke_test_symbols {
+            // SAFETY: the symbol is only looked up, never dereferenced or called.
+            unsafe { loaded.get::<*const ()>(symbol.as_bytes()) }.map_err(|err| C3Error::SmokeTestFailed {
+                symbol: Some(symbol.clone()),
+                reason: err.to_string(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// The extension of the library for the given OS, taking [C3FFI::override_extension] into account.
+    fn lib_extension(&self, os: &str) -> &str {
+        self.extension_overrides
+            .iter()
+            .find(|(override_os, mode, _)| override_os == os && *mode == self.linking_mode)
+            .map(|(_, _, ext)| ext.as_str())
+            .unwrap_or_else(|| lib_extension_for(os, &self.linking_mode))
+    }
+
+    /// Hashes everything that influences the produced library, including the contents of the source files.
+    fn config_hash(&self, name: &str, c3_target: &str) -> Result<u64, C3Error> {
+        let mut hasher = StableHasher::new();
+
+        name.hash(&mut hasher);
+        c3_target.hash(&mut hasher);
+        self.compiler.hash(&mut hasher);
+        if self.track_compiler {
+            resolve_program(&self.compiler)
+                .and_then(|compiler_path| fs::metadata(compiler_path).ok())
+                .and_then(|metadata| metadata.modified().ok())
+                .hash(&mut hasher);
+            self.compiler_version_info()?.hash(&mut hasher);
+        }
+        self.linking_mode.hash(&mut hasher);
+        self.optimization_level.hash(&mut hasher);
+        self.optimization_level_overrides.hash(&mut hasher);
+        self.debug_info.hash(&mut hasher);
+        self.single_module.hash(&mut hasher);
+        for file in &self.files {
+            file.hash(&mut hasher);
+            fs::read(file)?.hash(&mut hasher);
+        }
+        self.generated_sources.hash(&mut hasher);
+        self.c3_extensions.hash(&mut hasher);
+        self.features.hash(&mut hasher);
+        self.args.hash(&mut hasher);
+        self.environment_variables.hash(&mut hasher);
+        self.phase_environment_variables.hash(&mut hasher);
+        self.linker_arguments.hash(&mut hasher);
+        self.compiled_lib_dirs.hash(&mut hasher);
+        self.compiled_libs.hash(&mut hasher);
+        self.c3_lib_dirs.hash(&mut hasher);
+        self.c3_libs.hash(&mut hasher);
+        self.extension_overrides.hash(&mut hasher);
+        self.architecture_overrides.hash(&mut hasher);
+        self.stack_size.hash(&mut hasher);
+        self.merged_libs.hash(&mut hasher);
+        self.reproducible.hash(&mut hasher);
+        self.codegen_seed.hash(&mut hasher);
+        self.kept_symbols.hash(&mut hasher);
+        self.link_dead_code.hash(&mut hasher);
+        self.reloc_model.hash(&mut hasher);
+        self.sanitizer.hash(&mut hasher);
+        self.libc.hash(&mut hasher);
+        self.panic_strategy.hash(&mut hasher);
+        self.panic_messages.hash(&mut hasher);
+        self.backtrace.hash(&mut hasher);
+        self.link_sysroot.hash(&mut hasher);
+        self.macos_sdk.hash(&mut hasher);
+        self.windows_sdk.hash(&mut hasher);
+        self.windows_crt.hash(&mut hasher);
+        self.project.hash(&mut hasher);
+        self.build_config.hash(&mut hasher);
+
+        Ok(hasher.finish())
+    }
+}
+
+impl Default for C3FFI {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether two environment variable names refer to the same variable, optionally ignoring case like Windows does.
+fn same_env_key(a: &str, b: &str, ignore_case: bool) -> bool {
+    if ignore_case {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+/// The absolute path of an executable program, looked up in `PATH` when it is a bare name like `c3c`.
+fn resolve_program(program: &str) -> Option<PathBuf> {
+    let program_path = Path::new(program);
+    if program_path.components().count() > 1 {
+        return fs::canonicalize(program_path).ok().filter(|path| is_executable(path));
+    }
+
+    let names = if cfg!(windows) {
+        vec![program.to_string(), format!("{}.exe", program)]
+    } else {
+        vec![program.to_string()]
+    };
+    env::split_paths(&env::var_os("PATH")?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| is_executable(candidate))
+        .and_then(|candidate| fs::canonicalize(candidate).ok())
+}
+
+/// Whether the path is a file that can be executed, which on Windows only requires it to exist.
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The path of a source file relative to `CARGO_MANIFEST_DIR`, or without its root if it lies outside, with `..` turned
+/// into `__`, so it can be recreated below another directory without colliding with files of the same name.
+fn manifest_relative_path(file: &Path) -> Result<PathBuf, C3Error> {
+    let manifest_dir = PathBuf::from(var("CARGO_MANIFEST_DIR")?);
+    let file = file.strip_prefix(&manifest_dir).unwrap_or(file);
+
+    Ok(file
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name),
+            Component::ParentDir => Some("__".as_ref()),
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => None,
+        })
+        .collect())
+}
+
+/// Quotes a string for a POSIX shell, leaving it bare when that is unambiguous.
+fn shell_quote(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || matches!(char, '-' | '_' | '.' | '/' | '=' | ':' | ',' | '+'));
+    if is_plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// The path leading from the `from` directory to `to`, both absolute, or [None] if they share no common root.
+fn relative_path(from: &Path, to: &Path) -> Option<PathBuf> {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(from, to)| from == to).count();
+    if common == 0 {
+        return None;
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    Some(relative)
+}
+
+/// Applies [C3FFI::memory_limit] to the command by lowering `RLIMIT_AS` in the child before it executes.
+#[cfg(target_os = "linux")]
+fn limit_memory(command: &mut Command, bytes: u64) {
+    use std::{ffi::c_ulong, os::unix::process::CommandExt};
+
+    #[repr(C)]
+    struct Rlimit {
+        current: c_ulong,
+        max: c_ulong,
+    }
+
+    // `RLIMIT_AS` on every Linux architecture except MIPS, where it is 6.
+    #[cfg(not(any(target_arch = "mips", target_arch = "mips64")))]
+    const RLIMIT_AS: i32 = 9;
+    #[cfg(any(target_arch = "mips", target_arch = "mips64"))]
+    const RLIMIT_AS: i32 = 6;
+
+    unsafe extern "C" {
+        fn setrlimit(resource: i32, rlim: *const Rlimit) -> i32;
+    }
+
+    let bytes = c_ulong::try_from(bytes).unwrap_or(c_ulong::MAX);
+    let limit = Rlimit { current: bytes, max: bytes };
+    // SAFETY: setrlimit is async-signal-safe and `limit` outlives the call, as it is moved into the closure.
+    unsafe {
+        command.pre_exec(move || {
+            if setrlimit(RLIMIT_AS, &limit) == 0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn limit_memory(_command: &mut Command, _bytes: u64) {
+    println!("cargo::warning=memory_limit is only supported when building on Linux, ignoring it");
+}
+
+/// The builder method covering a raw c3c argument or subcommand, for the advice [C3FFI::attempt_compilation] gives
+/// about [C3FFI::arg] and [C3FFI::subcommand].
+fn typed_alternative(arg: &str) -> Option<&'static str> {
+    let method = match arg {
+        "-O0" | "-O1" | "-O2" | "-O3" | "-O4" | "-O5" | "-Os" | "-Oz" => "optimization_level",
+        "-g" | "-g0" => "debug_info",
+        "-v" | "-vv" | "-vvv" => "compiler_verbosity",
+        "static-lib" | "dynamic-lib" => "linking_mode",
+        "compile-only" => "check_only",
+        "--target" => "c3_target",
+        "--libdir" => "c3_lib_dir",
+        "--lib" => "c3_lib",
+        "-z" => "linker_argument",
+        "--build-dir" => "intermediates_dir",
+        "--emit-llvm" | "--llvm-out" => "emit_llvm",
+        "--emit-asm" | "--asm-out" | "-S" => "emit_asm",
+        "--no-strip-unused" => "link_dead_code",
+        "--threads" => "threads",
+        "--macossdk" => "macos_sdk",
+        "--winsdk" => "windows_sdk",
+        _ if arg.starts_with("--wincrt=") => "windows_crt",
+        _ if arg.starts_with("-D") => "feature",
+        _ if arg.starts_with("-L") => "compiled_lib_dir",
+        _ if arg.starts_with("-l") => "compiled_lib",
+        _ if arg.starts_with("--sanitize=") => "sanitizer",
+        _ if arg.starts_with("--reloc") => "reloc_model",
+        _ if arg.starts_with("--linux-libc=") => "libc",
+        _ if arg.starts_with("--panic-msg=") => "panic_messages",
+        _ if arg.starts_with("--show-backtrace=") => "backtrace",
+        _ if arg.starts_with("--single-module") => "single_module",
+        _ => return None,
+    };
+    Some(method)
+}
+
+/// Parses the value half of a `.env` line, removing quotes, escapes and trailing comments.
+fn parse_env_value(value: &str) -> String {
+    if let Some(quoted) = value.strip_prefix('\'') {
+        return quoted.split('\'').next().unwrap_or_default().to_string();
+    }
+
+    if let Some(quoted) = value.strip_prefix('"') {
+        let mut parsed = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => parsed.push('\n'),
+                    Some(escaped) => parsed.push(escaped),
+                    None => parsed.push('\\'),
+                },
+                c => parsed.push(c),
+            }
+        }
+        return parsed;
+    }
+
+    match value.find(" #") {
+        Some(comment) => value[..comment].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Hashes raw bytes with [StableHasher].
+fn content_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Quotes and escapes a string for use in JSON.
+fn json_string(string: &str) -> String {
+    let mut json = String::with_capacity(string.len() + 2);
+    json.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// An error or warning c3c reported, as rendered by [ErrorFormat::GithubActions], [ErrorFormat::Json] and
+/// [C3FFI::diagnostics_json].
+struct Diagnostic<'a> {
+    severity: DiagnosticSeverity,
+    /// The file, line and column, when c3c reported them.
+    location: Option<(&'a str, u32, u32)>,
+    message: &'a str,
+}
+
+impl<'a> Diagnostic<'a> {
+    /// Parses a line like `(src/thing.c3:3:5) Error: message`, the location being optional.
+    fn parse(line: &'a str) -> Option<Self> {
+        let line = line.trim();
+        let (severity, message) = if let Some(index) = line.find("Error:") {
+            (DiagnosticSeverity::Error, &line[index..])
+        } else if let Some(index) = line.find("Warning:") {
+            (DiagnosticSeverity::Warning, &line[index..])
+        } else {
+            return None;
+        };
+        let message = message.split_once(':').map_or(message, |(_, message)| message.trim());
+
+        let location = line
+            .strip_prefix('(')
+            .and_then(|line| line.split_once(')'))
+            .and_then(|(location, _)| {
+                let mut parts = location.rsplitn(3, ':');
+                let column = parts.next()?.parse().ok()?;
+                let line = parts.next()?.parse().ok()?;
+                Some((parts.next()?, line, column))
+            });
+
+        Some(Diagnostic {
+            severity,
+            location,
+            message,
+        })
+    }
+
+    /// The diagnostic as a JSON object, as written by [ErrorFormat::Json] and [C3FFI::diagnostics_json].
+    fn to_json(&self) -> String {
+        let severity = if self.severity == DiagnosticSeverity::Error { "error" } else { "warning" };
+        let (file, line, column) = match &self.location {
+            Some((file, line, column)) => (json_string(file), line.to_string(), column.to_string()),
+            None => ("null".to_string(), "null".to_string(), "null".to_string()),
+        };
+
+        format!(
+            "{{\"severity\": {}, \"file\": {}, \"line\": {}, \"column\": {}, \"message\": {}}}",
+            json_string(severity),
+            file,
+            line,
+            column,
+            json_string(self.message)
+        )
+    }
+}
+
+/// Finds the value of a `Key: value` line in the output of `c3c --version`, where `key` is a prefix of the key.
+fn version_field<'a>(version_info: &'a str, key: &str) -> Option<&'a str> {
+    version_info.lines().find_map(|line| {
+        let (line_key, value) = line.split_once(':')?;
+        line_key.trim().starts_with(key).then(|| value.trim())
+    })
+}
+
+/// The targets listed by `c3c --list-targets`, skipping headings like `Available targets:`.
+fn available_targets(target_list: &str) -> Vec<String> {
+    target_list
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.ends_with(':') && !line.contains(char::is_whitespace))
+        .map(String::from)
+        .collect()
+}
+
+/// Parses a version like `0.7.6`, `0.7` or `0.6.8 (Pre-release)` into its components, missing ones being `0` and
+/// anything after the digits of a component, like `-dev`, being ignored.
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut components = version.split_whitespace().next()?.split('.').map(|component| {
+        let digits = component.len() - component.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        component[..digits].parse::<u32>().ok()
+    });
+
+    let major = components.next()??;
+    let minor = components.next().unwrap_or(Some(0))?;
+    let patch = components.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+/// Finds the name of the first module declared in a C3 source file, e.g. `foo::bar` for `module foo::bar @private;`.
+fn c3_module_name(source: &str) -> Option<String> {
+    source.lines().map(str::trim).find_map(|line| {
+        let module = line.strip_prefix("module")?;
+        if !module.starts_with(char::is_whitespace) {
+            return None;
+        }
+
+        let module: String = module
+            .trim_start()
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == ':')
+            .collect();
+        (!module.is_empty()).then_some(module)
+    })
+}
+
+/// Reads an environment variable, usually one provided by cargo to build scripts.
+fn var(name: &str) -> Result<String, C3Error> {
+    env::var(name).map_err(|err| C3Error::EnvironmentVariable {
+        name: name.to_string(),
+        source: err,
+    })
+}
+
+/// The reserved library name [C3FFI::check] compiles under, so it cannot clash with a library of the crate.
+const CHECK_NAME: &str = "__c3ne_check";
+
+/// How architectures in Rust target triples are named by c3c.
+const ARCHITECTURES: &[(&str, &str)] = &[
+    ("x86_64", "x64"),
+    ("i386", "x86"),
+    ("i586", "x86"),
+    ("i686", "x86"),
+    ("aarch64", "aarch64"),
+    ("arm64", "aarch64"),
+    ("riscv32i", "riscv32"),
+    ("riscv32im", "riscv32"),
+    ("riscv32imc", "riscv32"),
+    ("riscv32imac", "riscv32"),
+    ("riscv32gc", "riscv32"),
+    ("riscv64gc", "riscv64"),
+    ("riscv64imac", "riscv64"),
+    ("wasm32", "wasm32"),
+    ("wasm64", "wasm64"),
+];
+
+/// Translates a Rust target triple, e.g. `x86_64-unknown-linux-gnu`, into the equivalent c3c target, e.g. `linux-x64`.
+///
+/// Fails with [C3Error::UnsupportedTarget] for architectures c3c is not known to support.
+///
+///
+/// Example:
+/// ```rs
+/// assert_eq!(c3ne::rust_target_to_c3("i686-pc-windows-gnu").unwrap(), "mingw-x86");
+/// ```
+pub fn rust_target_to_c3(target: &str) -> Result<String, C3Error> {
+    translate_target(target, &[])
+}
+
+/// [rust_target_to_c3], taking the architecture overrides set with [C3FFI::override_arch] into account.
+fn translate_target(target: &str, architecture_overrides: &[(String, String)]) -> Result<String, C3Error> {
+    let target_split: Vec<&str> = target.split("-").collect();
+    let architecture = architecture_overrides
+        .iter()
+        .find(|(rust_arch, _)| rust_arch == target_split[0])
+        .map(|(_, c3_arch)| c3_arch.as_str())
+        .or_else(|| {
+            ARCHITECTURES
+                .iter()
+                .find(|(rust_arch, _)| *rust_arch == target_split[0])
+                .map(|(_, c3_arch)| *c3_arch)
+        })
+        .ok_or_else(|| C3Error::UnsupportedTarget(target.to_string()))?;
+    if architecture.starts_with("wasm") {
+        return Ok(architecture.to_string());
+    }
+
+    // The vendor and environment are optional, so look for the OS among every segment after the architecture.
+    let segments = &target_split[1..];
+    let os = if segments.iter().any(|segment| segment.starts_with("android")) {
+        "android"
+    } else {
+        segments
+            .iter()
+            .find_map(|segment| match *segment {
+                "windows" if segments.iter().any(|segment| matches!(*segment, "gnu" | "gnullvm")) => Some("mingw"),
+                "darwin" | "macos" => Some("macos"),
+                "none" => Some("elf"),
+                "linux" | "windows" | "ios" | "freebsd" | "netbsd" | "openbsd" => Some(*segment),
+                _ => None,
+            })
+            .ok_or_else(|| C3Error::UnsupportedTarget(target.to_string()))?
+    };
+
+    Ok(format!("{}-{}", os, architecture))
+}
+
+/// Lists the files directly inside `dir` that have one of the given extensions, sorted by path.
+fn files_with_extensions(dir: &Path, extensions: &[&str]) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| extensions.contains(&extension))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Translates the function prototypes of a c3c-generated C header into Rust `extern "C"` declarations.
+fn c_header_to_rust(path: &Path, header: &str) -> String {
+    let mut declarations = String::new();
+    for line in header.lines().map(str::trim) {
+        if line.starts_with('#') || line.starts_with("typedef") || !line.ends_with(");") {
+            continue;
+        }
+
+        match c_prototype_to_rust(line) {
+            Some(declaration) => declarations.push_str(&format!("    {}\n", declaration)),
+            None => declarations.push_str(&format!("    // Skipped, unsupported signature: {}\n", line)),
+        }
+    }
+
+    format!(
+        "// Generated by c3ne from {}.\nunsafe extern \"C\" {{\n{}}}\n",
+        path.display(),
+        declarations
+    )
+}
+
+/// Rust keywords which are valid C identifiers, and so may show up as parameter names in c3c-generated headers.
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "crate", "dyn", "final", "fn", "gen", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "self", "super", "trait", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "yield",
+];
+
+/// Translates a single C function prototype, such as `extern int32_t add(int32_t a, int32_t b);`.
+fn c_prototype_to_rust(prototype: &str) -> Option<String> {
+    let prototype = prototype.trim_start_matches("extern ").trim_end_matches(';');
+    let (signature, parameters) = prototype.split_once('(')?;
+    let parameters = parameters.strip_suffix(')')?;
+
+    let (return_type, name) = split_c_declaration(signature)?;
+    let return_type = match return_type.as_str() {
+        "void" => String::new(),
+        return_type => format!(" -> {}", c_type_to_rust(return_type)?),
+    };
+
+    let mut rust_parameters = Vec::new();
+    if !matches!(parameters.trim(), "" | "void") {
+        for (index, parameter) in parameters.split(',').enumerate() {
+            if parameter.trim() == "..." {
+                return None;
+            }
+
+            let (parameter_type, parameter_name) = match split_c_declaration(parameter) {
+                Some((parameter_type, parameter_name))
+                    if c_type_to_rust(&parameter_type).is_some() && c_type_to_rust(&parameter_name).is_none() =>
+                {
+                    (parameter_type, parameter_name)
+                }
+                // Unnamed parameters, e.g. `int32_t` or `unsigned int`.
+                _ => (parameter.trim().to_string(), format!("arg{}", index)),
+            };
+            let parameter_name = if RUST_KEYWORDS.contains(&parameter_name.as_str()) {
+                format!("{}_", parameter_name)
+            } else {
+                parameter_name
+            };
+            rust_parameters.push(format!("{}: {}", parameter_name, c_type_to_rust(&parameter_type)?));
+        }
+    }
+
+    Some(format!("pub fn {}({}){};", name, rust_parameters.join(", "), return_type))
+}
+
+/// Splits a C declaration such as `const char* name` into its type (`const char*`) and name (`name`).
+fn split_c_declaration(declaration: &str) -> Option<(String, String)> {
+    let declaration = declaration.trim();
+    let name_start = declaration
+        .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .map_or(0, |index| index + 1);
+    let (declaration_type, name) = declaration.split_at(name_start);
+    if name.is_empty() || declaration_type.trim().is_empty() {
+        return None;
+    }
+
+    Some((declaration_type.trim().to_string(), name.to_string()))
+}
+
+/// Maps a primitive C type, or a pointer to one, to its Rust equivalent.
+fn c_type_to_rust(c_type: &str) -> Option<String> {
+    let c_type = c_type.trim();
+    if let Some(pointee) = c_type.strip_suffix('*') {
+        let pointee = pointee.trim();
+        let (mutability, pointee) = match pointee.strip_prefix("const ") {
+            Some(pointee) => ("const", pointee),
+            None => ("mut", pointee),
+        };
+        let pointee = match pointee.trim() {
+            "void" => "::core::ffi::c_void".to_string(),
+            pointee => c_type_to_rust(pointee).unwrap_or_else(|| "::core::ffi::c_void".to_string()),
+        };
+        return Some(format!("*{} {}", mutability, pointee));
+    }
+
+    let rust_type = match c_type {
+        "bool" | "_Bool" => "bool",
+        "char" => "::core::ffi::c_char",
+        "signed char" | "int8_t" => "i8",
+        "unsigned char" | "uint8_t" => "u8",
+        "short" | "int16_t" => "i16",
+        "unsigned short" | "uint16_t" => "u16",
+        "int" | "int32_t" => "i32",
+        "unsigned" | "unsigned int" | "uint32_t" => "u32",
+        "long" => "::core::ffi::c_long",
+        "unsigned long" => "::core::ffi::c_ulong",
+        "long long" | "int64_t" => "i64",
+        "unsigned long long" | "uint64_t" => "u64",
+        "float" => "f32",
+        "double" => "f64",
+        "size_t" | "uintptr_t" => "usize",
+        "ptrdiff_t" | "intptr_t" | "ssize_t" => "isize",
+        _ => return None,
+    };
+
+    Some(rust_type.to_string())
+}
+
+/// The file extension c3c gives a library built for the given OS (as it appears in the C3 target, e.g. `linux` or
+/// `mingw`) and linking mode.
+///
+///
+/// Example:
+/// ```rs
+/// assert_eq!(c3ne::lib_extension_for("macos", &LinkingMode::Dynamic), "dylib");
+/// ```
+pub fn lib_extension_for(os: &str, mode: &LinkingMode) -> &'static str {
+    match (mode, os) {
+        (_, "wasm32" | "wasm64") => "wasm",
+        (LinkingMode::Static, "windows" | "mingw") => "lib",
+        (LinkingMode::Static, _) => "a",
+        (LinkingMode::Dynamic, "windows" | "mingw") => "dll",
+        (LinkingMode::Dynamic, "macos" | "ios") => "dylib",
+        (LinkingMode::Dynamic, _) => "so",
+    }
+}
+
+/// 64-bit FNV-1a, used over [std::hash::DefaultHasher] since its output must not change between Rust releases.
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Errors that can occur while compiling C3 code.
+#[derive(Debug)]
+pub enum C3Error {
+    /// An environment variable was missing or not valid unicode, usually one cargo provides to build scripts.
+    EnvironmentVariable { name: String, source: VarError },
+    /// The builder was configured in a way that cannot work.
+    InvalidConfiguration(String),
+    /// The compiler set with [C3FFI::compiler] is neither an executable file nor found in `PATH`.
+    CompilerNotFound(String),
+    /// A source file added with [C3FFI::file] does not exist, checked before c3c is run.
+    MissingSourceFile(PathBuf),
+    /// Two features declared with [C3FFI::conflicting_features] were both turned on.
+    ConflictingFeatures(String, String),
+    /// c3c is older than the version required with [C3FFI::require_min_version].
+    CompilerTooOld { found: String, required: String },
+    /// The standard library bundled with c3c is not the version required with [C3FFI::require_stdlib_version].
+    StdlibVersionMismatch { found: String, required: String },
+    /// c3c does not support the target it would build for, found with [C3FFI::verify_target], with the targets it does
+    /// support.
+    TargetNotInstalled { target: String, available: Vec<String> },
+    /// The Rust target triple has no c3c equivalent, see [rust_target_to_c3].
+    UnsupportedTarget(String),
+    /// c3c did not succeed, with the exit code if it exited normally and what it printed to stderr, rendered according
+    /// to [C3FFI::error_format].
+    CompilationFailed {
+        code: Option<i32>,
+        stderr: String,
+        format: ErrorFormat,
+    },
+    /// A command registered with [C3FFI::prebuild_command] failed, with the exit code if it exited normally and what it
+    /// printed.
+    PrebuildFailed { program: String, code: Option<i32>, output: String },
+    /// The tool merging the libraries given to [C3FFI::merge_with] into the C3 library failed, with the exit code if it
+    /// exited normally and what it printed.
+    ArchiveMergeFailed { program: String, code: Option<i32>, output: String },
+    /// [C3FFI::smoke_test] could not load the library, when `symbol` is [None], or could not find the symbol in it.
+    SmokeTestFailed { symbol: Option<String>, reason: String },
+    /// The library references symbols that nothing provides, found with [C3FFI::verify_link].
+    UndefinedSymbols(Vec<String>),
+    /// Several libraries built with [C3FFI::compile_many] failed, each with the name of the library.
+    Multiple(Vec<(String, C3Error)>),
+    /// An I/O error occurred, e.g. while spawning c3c or reading a source file.
+    Io(io::Error),
+}
+
+impl Display for C3Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            C3Error::EnvironmentVariable { name, source } => {
+                write!(f, "failed to read environment variable {}: {}", name, source)
+            }
+            C3Error::InvalidConfiguration(message) => write!(f, "invalid configuration: {}", message),
+            C3Error::CompilerNotFound(compiler) => write!(
+                f,
+                "compiler {} was not found, install c3c and add it to PATH or point compiler() at it",
+                compiler
+            ),
+            C3Error::MissingSourceFile(file) => write!(f, "source file {} does not exist", file.display()),
+            C3Error::ConflictingFeatures(a, b) => {
+                write!(f, "features {} and {} cannot be turned on together", a, b)
+            }
+            C3Error::CompilerTooOld { found, required } => write!(
+                f,
+                "c3c is version {}, but at least version {} is required",
+                if found.is_empty() { "unknown" } else { found },
+                required
+            ),
+            C3Error::StdlibVersionMismatch { found, required } => write!(
+                f,
+                "c3c's standard library is version {}, but version {} is required",
+                found, required
+            ),
+            C3Error::TargetNotInstalled { target, available } => write!(
+                f,
+                "c3c does not support target {}, available targets are: {}",
+                target,
+                available.join(", ")
+            ),
+            C3Error::UnsupportedTarget(target) => write!(
+                f,
+                "target {} is not supported by c3c, use override_arch or c3_target to map it",
+                target
+            ),
+            C3Error::CompilationFailed {
+                code,
+                stderr,
+                format: ErrorFormat::GithubActions,
+            } => {
+                let mut annotated = false;
+                for diagnostic in stderr.lines().filter_map(Diagnostic::parse) {
+                    let severity = if diagnostic.severity == DiagnosticSeverity::Error { "error" } else { "warning" };
+                    match &diagnostic.location {
+                        Some((file, line, column)) => writeln!(
+                            f,
+                            "::{} file={},line={},col={}::{}",
+                            severity, file, line, column, diagnostic.message
+                        )?,
+                        None => writeln!(f, "::{}::{}", severity, diagnostic.message)?,
+                    }
+                    annotated |= diagnostic.severity == DiagnosticSeverity::Error;
+                }
+                if !annotated {
+                    match code {
+                        Some(code) => write!(f, "::error::c3c exited with code {}", code)?,
+                        None => write!(f, "::error::c3c was terminated by a signal")?,
+                    }
+                }
+                Ok(())
+            }
+            C3Error::CompilationFailed {
+                code,
+                stderr,
+                format: ErrorFormat::Json,
+            } => {
+                let diagnostics: Vec<String> = stderr
+                    .lines()
+                    .filter_map(Diagnostic::parse)
+                    .map(|diagnostic| diagnostic.to_json())
+                    .collect();
+                write!(
+                    f,
+                    "{{\"code\": {}, \"diagnostics\": [{}], \"stderr\": {}}}",
+                    code.map_or_else(|| "null".to_string(), |code| code.to_string()),
+                    diagnostics.join(", "),
+                    json_string(stderr)
+                )
+            }
+            C3Error::CompilationFailed { code, stderr, .. } => {
+                match code {
+                    Some(code) => write!(f, "c3c exited with code {}", code)?,
+                    None => write!(f, "c3c was terminated by a signal")?,
+                }
+                if !stderr.trim().is_empty() {
+                    write!(f, ":\n{}", stderr.trim_end())?;
+                }
+                Ok(())
+            }
+            C3Error::PrebuildFailed { program, code, output } => {
+                match code {
+                    Some(code) => write!(f, "prebuild command {} exited with code {}", program, code)?,
+                    None => write!(f, "prebuild command {} was terminated by a signal", program)?,
+                }
+                if !output.trim().is_empty() {
+                    write!(f, ":\n{}", output.trim_end())?;
+                }
+                Ok(())
+            }
+            C3Error::ArchiveMergeFailed { program, code, output } => {
+                match code {
+                    Some(code) => write!(f, "merging archives with {} failed with code {}", program, code)?,
+                    None => write!(f, "merging archives with {} was terminated by a signal", program)?,
+                }
+                if !output.trim().is_empty() {
+                    write!(f, ":\n{}", output.trim_end())?;
+                }
+                Ok(())
+            }
+            C3Error::SmokeTestFailed { symbol: Some(symbol), reason } => {
+                write!(f, "smoke test failed to find symbol {}: {}", symbol, reason)
+            }
+            C3Error::SmokeTestFailed { symbol: None, reason } => {
+                write!(f, "smoke test failed to load the library: {}", reason)
+            }
+            C3Error::UndefinedSymbols(symbols) => {
+                write!(f, "the library references undefined symbols: {}", symbols.join(", "))
+            }
+            C3Error::Multiple(errors) => {
+                write!(f, "{} libraries failed to compile", errors.len())?;
+                for (name, err) in errors {
+                    write!(f, "\n{}: {}", name, err)?;
+                }
+                Ok(())
+            }
+            C3Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for C3Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            C3Error::EnvironmentVariable { source, .. } => Some(source),
+            C3Error::Io(err) => Some(err),
+            C3Error::InvalidConfiguration(_)
+            | C3Error::CompilerNotFound(_)
+            | C3Error::MissingSourceFile(_)
+            | C3Error::ConflictingFeatures(_, _)
+            | C3Error::CompilerTooOld { .. }
+            | C3Error::StdlibVersionMismatch { .. }
+            | C3Error::TargetNotInstalled { .. }
+            | C3Error::UnsupportedTarget(_)
+            | C3Error::CompilationFailed { .. }
+            | C3Error::PrebuildFailed { .. }
+            | C3Error::ArchiveMergeFailed { .. }
+            | C3Error::SmokeTestFailed { .. }
+            | C3Error::UndefinedSymbols(_)
+            | C3Error::Multiple(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for C3Error {
+    fn from(err: io::Error) -> Self {
+        C3Error::Io(err)
+    }
+}
+
+/// Describes the outcome of a successful call to [C3FFI::attempt_compilation].
+#[derive(Debug, Clone, Default)]
+pub struct CompileResult {
+    /// The path to the produced library, or the first one when using [C3FFI::split_output].
+    pub library: PathBuf,
+    /// The paths to every produced library, more than one only when using [C3FFI::split_output].
+    pub libraries: Vec<PathBuf>,
+    /// The import library Rust links against for a Windows DLL, produced alongside it when using [LinkingMode::Dynamic].
+    pub import_library: Option<PathBuf>,
+    /// The object files c3c produced while building the library.
+    pub objects: Vec<PathBuf>,
+    /// The C headers c3c generated for the library, leaving out any other headers in `OUT_DIR`.
+    pub headers: Vec<PathBuf>,
+    /// How many warnings c3c reported.
+    pub warnings: usize,
+    /// How long the compilation took.
+    pub duration: Duration,
+    /// Whether the compilation was skipped, in which case every other field is empty.
+    pub skipped: bool,
+}
+
+/// A compilation prepared up to running c3c, see [C3FFI::compile_library].
+struct PendingCompilation {
+    name: String,
+    started: Instant,
+    c3_target: String,
+    out_dir: String,
+    canonical_name: String,
+    out_name: String,
+    obj_dir: PathBuf,
+    llvm_ir_dir: PathBuf,
+    asm_dir: PathBuf,
+    staging_dir: PathBuf,
+    generated_files: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+    args: Vec<String>,
+    extension: String,
+    is_windows_dll: bool,
+    cache_stamp: PathBuf,
+    cache_digest: Option<String>,
+    /// [None] when [C3FFI::cache] found the library up to date.
+    command: Option<Command>,
+}
+
+impl PendingCompilation {
+    /// Runs c3c, or pretends it succeeded without printing anything when the library is cached.
+    fn run(&mut self) -> io::Result<Output> {
+        match &mut self.command {
+            Some(command) => command.output(),
+            None => Ok(Output {
+                status: ExitStatus::default(),
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            }),
+        }
+    }
+}
+
+/// A temporary directory created by [C3FFI::compile_to_temp], deleted with everything in it when dropped.
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// The path to the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Describes the outcome of a successful call to [C3FFI::compile_bundle].
+#[derive(Debug, Clone, Default)]
+pub struct CompileBundle {
+    /// The outcome of building the static library.
+    pub static_library: CompileResult,
+    /// The outcome of building the dynamic library.
+    pub dynamic_library: CompileResult,
+    static_link_directives: Vec<String>,
+    dynamic_link_directives: Vec<String>,
+}
+
+impl CompileBundle {
+    /// Tells cargo to link the library built with the given [LinkingMode].
+    pub fn emit_link_directives(&self, linking_mode: LinkingMode) {
+        let link_directives = match linking_mode {
+            LinkingMode::Static => &self.static_link_directives,
+            LinkingMode::Dynamic => &self.dynamic_link_directives,
+        };
+        for link_directive in link_directives {
+            println!("{}", link_directive);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkingMode {
+    /// Equivalent to calling c3c with the `static-lib` command.
+    Static,
+    /// Equivalent to calling c3c with the `dynamic-lib` command.
+    Dynamic,
+}
+
+#[derive(Hash)]
+pub enum OptimizationLevel {
+    /// Safe, no optimizations, emit debug info.
+    ///
+    /// Equivalent to calling c3c with `-O0`.
+    O0,
+    /// Safe, high optimization, emit debug info.
+    ///
+    /// Equivalent to calling c3c with `-O1`.
+    O1,
+    /// Unsafe, high optimization, emit debug info.
+    ///
+    /// Equivalent to calling c3c with `-O2`.
+    O2,
+    /// Unsafe, high optimization, single module, emit debug info.
+    ///
+    /// Equivalent to calling c3c with `-O3`.
+    O3,
+    /// Unsafe, highest optimization, relaxed maths, single module, emit debug info, no panic messages.
+    ///
+    /// Equivalent to calling c3c with `-O4`.
+    O4,
+    /// Unsafe, highest optimization, fast maths, single module, emit debug info, no panic messages, no backtrace.
+    ///
+    /// Equivalent to calling c3c with `-O5`.
+    O5,
+    /// Unsafe, high optimization, small code, single module, no debug info, no panic messages.
+    ///
+    /// Equivalent to calling c3c with `-Os`.
+    Os,
+    /// Unsafe, high optimization, tiny code, single module, no debug info, no panic messages, no backtrace.
+    ///
+    /// Equivalent to calling c3c with `-Oz`.
+    Oz,
+}
+
+impl OptimizationLevel {
+    pub fn to_str(&self) -> &str {
+        match self {
+            OptimizationLevel::O0 => "O0",
+            OptimizationLevel::O1 => "O1",
+            OptimizationLevel::O2 => "O2",
+            OptimizationLevel::O3 => "O3",
+            OptimizationLevel::O4 => "O4",
+            OptimizationLevel::O5 => "O5",
+            OptimizationLevel::Os => "Os",
+            OptimizationLevel::Oz => "Oz",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiagnosticSeverity {
+    /// The line reports an error.
+    Error,
+    /// The line reports a warning.
+    Warning,
+    /// The line adds information to a previous error or warning.
+    Note,
+    /// The line is not a diagnostic, e.g. a snippet of the offending source code.
+    None,
+}
+
+/// How a failed compilation is rendered, set with [C3FFI::error_format].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorFormat {
+    /// The exit code followed by everything c3c printed to stderr.
+    Plain,
+    /// One GitHub Actions workflow command per error and warning, e.g. `::error file=src/thing.c3,line=3,col=5::...`, so
+    /// they show up as annotations on the offending lines.
+    GithubActions,
+    /// A single JSON object with the exit code, the errors and warnings with their locations, and the raw stderr.
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RelocModel {
+    /// The target's default relocation model.
+    ///
+    /// Equivalent to calling c3c without `--reloc`.
+    Default,
+    /// Position-independent code, usable in shared libraries and PIE executables.
+    ///
+    /// Equivalent to calling c3c with `--reloc=pic`.
+    Pic,
+    /// Position-independent code that may assume it ends up in an executable.
+    ///
+    /// Equivalent to calling c3c with `--reloc=pie`.
+    Pie,
+    /// Code that must be loaded at a fixed address.
+    ///
+    /// Equivalent to calling c3c with `--reloc=none`.
+    Static,
+}
+
+impl RelocModel {
+    pub fn to_flag(&self) -> Option<&str> {
+        match self {
+            RelocModel::Default => None,
+            RelocModel::Pic => Some("--reloc=pic"),
+            RelocModel::Pie => Some("--reloc=pie"),
+            RelocModel::Static => Some("--reloc=none"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sanitizer {
+    /// Detects out-of-bounds accesses, use-after-free and similar memory errors.
+    ///
+    /// Equivalent to calling c3c with `--sanitize=address`.
+    Address,
+    /// Detects data races between threads.
+    ///
+    /// Equivalent to calling c3c with `--sanitize=thread`.
+    Thread,
+    /// Detects reads of uninitialized memory, only supported by Clang on a few 64-bit targets.
+    ///
+    /// Equivalent to calling c3c with `--sanitize=memory`.
+    Memory,
+}
+
+impl Sanitizer {
+    pub fn to_str(&self) -> &str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Thread => "thread",
+            Sanitizer::Memory => "memory",
+        }
+    }
+
+    pub fn to_flag(&self) -> String {
+        format!("--sanitize={}", self.to_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ObjectFormat {
+    /// ELF, used by Linux, Android, the BSDs and bare-metal targets.
+    Elf,
+    /// Mach-O, used by Apple targets.
+    MachO,
+    /// COFF, used by Windows targets.
+    Coff,
+    /// WebAssembly objects.
+    Wasm,
+}
+
+impl ObjectFormat {
+    /// The format c3c emits for the given c3c target, e.g. `linux-x64`.
+    pub fn of_target(c3_target: &str) -> ObjectFormat {
+        match c3_target.split('-').next().unwrap_or_default() {
+            os if os.starts_with("wasm") => ObjectFormat::Wasm,
+            "windows" | "mingw" => ObjectFormat::Coff,
+            "macos" | "ios" => ObjectFormat::MachO,
+            _ => ObjectFormat::Elf,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LibcKind {
+    /// The GNU C library, for `*-linux-gnu*` targets.
+    ///
+    /// Equivalent to calling c3c with `--linux-libc=gnu`, and links libc dynamically.
+    Glibc,
+    /// musl, for `*-musl*` targets.
+    ///
+    /// Equivalent to calling c3c with `--linux-libc=musl`, and links libc statically without bundling it, like the Rust
+    /// standard library does.
+    Musl,
+    /// newlib, for embedded `*-none-*` and `*newlib*` targets.
+    ///
+    /// c3c has no flag for newlib, so this only links libc statically.
+    Newlib,
+}
+
+impl LibcKind {
+    pub fn to_flag(&self) -> Option<&str> {
+        match self {
+            LibcKind::Glibc => Some("--linux-libc=gnu"),
+            LibcKind::Musl => Some("--linux-libc=musl"),
+            LibcKind::Newlib => None,
+        }
+    }
+
+    /// The value of the `rustc-link-lib` directive linking this libc.
+    pub fn link_lib(&self) -> &str {
+        match self {
+            LibcKind::Glibc => "dylib=c",
+            LibcKind::Musl => "static:-bundle=c",
+            LibcKind::Newlib => "static=c",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WinCrt {
+    /// No C runtime is linked, for libraries that do not use the C3 standard library's libc parts.
+    ///
+    /// Equivalent to calling c3c with `--wincrt=none`.
+    None,
+    /// The C runtime is linked statically, matching Rust with the `crt-static` target feature.
+    ///
+    /// Equivalent to calling c3c with `--wincrt=static`.
+    Static,
+    /// The C runtime is linked dynamically, matching Rust's default.
+    ///
+    /// Equivalent to calling c3c with `--wincrt=dynamic`.
+    Dynamic,
+}
+
+impl WinCrt {
+    pub fn to_flag(&self) -> &str {
+        match self {
+            WinCrt::None => "--wincrt=none",
+            WinCrt::Static => "--wincrt=static",
+            WinCrt::Dynamic => "--wincrt=dynamic",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// The c3c invocation building the library.
+    Compile,
+    /// The `c3c --version` and `c3c --list-targets` invocations used by [C3FFI::require_stdlib_version],
+    /// [C3FFI::require_min_version], [C3FFI::verify_target] and [C3FFI::write_build_manifest].
+    VersionQuery,
+    /// The C compiler invocation performed by [C3FFI::verify_link].
+    VerifyLink,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PanicStrategy {
+    /// Panics print their message to stderr before terminating the process.
+    ///
+    /// Equivalent to calling c3c with `--panic-msg=yes`.
+    Unwind,
+    /// Panics terminate the process immediately, without printing anything.
+    ///
+    /// Equivalent to calling c3c with `--panic-msg=no`.
+    Abort,
+    /// Leaves the panic behavior to c3c, which depends on the optimization level.
+    Default,
+}
+
+impl PanicStrategy {
+    pub fn to_flag(&self) -> Option<&str> {
+        match self {
+            PanicStrategy::Unwind => Some("--panic-msg=yes"),
+            PanicStrategy::Abort => Some("--panic-msg=no"),
+            PanicStrategy::Default => None,
+        }
+    }
+}
+
+/// Alternative name for [C3FFI], provided for users looking for a more standard naming approach.
+pub type Build = C3FFI;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh, empty directory for the test with the given name, deleted again when dropped.
+    fn temp_dir(test: &str) -> TempDir {
+        let dir = TempDir {
+            path: env::temp_dir().join(format!("c3ne-test-{}-{}", test, process::id())),
+        };
+        let _ = fs::remove_dir_all(&dir.path);
+        fs::create_dir_all(&dir.path).unwrap();
+        dir
+    }
+
+    /// A builder for `linux-x64` building into `dir`, so neither `TARGET` nor `OUT_DIR` are needed.
+    fn builder(dir: &TempDir) -> C3FFI {
+        let mut build = C3FFI::new();
+        build.c3_target("linux-x64");
+        build.out_dir = Some(dir.path().to_path_buf());
+        build
+    }
+
+    /// Writes a source file into `dir`, returning its path.
+    fn source(dir: &TempDir, name: &str, contents: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// The arguments c3c would be run with to build `thing`, without the compiler itself.
+    fn args(build: &C3FFI) -> Vec<String> {
+        build.command_line("thing").unwrap()[1..].to_vec()
+    }
+
+    /// Whether `needle` appears in `args` as consecutive arguments.
+    fn contains_args(args: &[String], needle: &[&str]) -> bool {
+        args.windows(needle.len()).any(|window| window == needle)
+    }
+
+    /// Installs a stand-in for c3c into `dir`, returning its path.
+    ///
+    /// It appends its arguments to `c3c.args` in `dir`, one per line and followed by `--`, then runs `script`, which
+    /// can use `$command`, `$out_dir`, `$out_name` and `$obj_dir`, and finally creates an empty library where c3c would
+    /// put it.
+    #[cfg(unix)]
+    fn fake_c3c(dir: &TempDir, script: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.path().join("c3c");
+        fs::write(
+            &path,
+            format!(
+                r#"#!/bin/sh
+printf '%s\n' "$@" -- >> '{}/c3c.args'
+command=$1
+while [ $# -gt 0 ]; do
+    case $1 in
+        --output-dir) out_dir=$2 ;;
+        -o) out_name=$2 ;;
+        --obj-out) obj_dir=$2 ;;
+    esac
+    shift
+done
+{}
+case $command in
+    static-lib) mkdir -p "$out_dir" && touch "$out_dir/$out_name.a" ;;
+    dynamic-lib) mkdir -p "$out_dir" && touch "$out_dir/$out_name.so" ;;
+esac
+"#,
+                dir.path().display(),
+                script
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.display().to_string()
+    }
+
+    /// The arguments of every run of the stand-in from [fake_c3c] in `dir`, in order.
+    #[cfg(unix)]
+    fn recorded_args(dir: &TempDir) -> Vec<Vec<String>> {
+        let recorded = fs::read_to_string(dir.path().join("c3c.args")).unwrap_or_default();
+        let mut runs = Vec::new();
+        let mut run = Vec::new();
+        for line in recorded.lines() {
+            if line == "--" {
+                runs.push(std::mem::take(&mut run));
+            } else {
+                run.push(line.to_string());
+            }
+        }
+        runs
+    }
+
+    #[test]
+    fn stable_hasher_is_fnv1a() {
+        assert_eq!(content_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(content_hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(content_hash(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn content_addressed_names_the_library_after_the_config_hash() {
+        let dir = temp_dir("content-addressed-name");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.file(&file).content_addressed(true);
+
+        let hash = build.config_hash("thing", "linux-x64").unwrap();
+        assert!(contains_args(&args(&build), &["-o", &format!("libthing-{:016x}", hash)]));
+
+        fs::write(&file, "module thing; fn void f() {}").unwrap();
+        assert_ne!(build.config_hash("thing", "linux-x64").unwrap(), hash);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn content_addressed_copies_the_library_to_its_canonical_name() {
+        let dir = temp_dir("content-addressed-copy");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).file(&file).content_addressed(true);
+        let hash = build.config_hash("thing", "linux-x64").unwrap();
+
+        let result = build.attempt_compilation("thing").unwrap();
+        assert_eq!(result.library, dir.path().join("libthing.a"));
+        assert!(result.library.exists());
+        assert!(dir.path().join(format!("libthing-{:016x}.a", hash)).exists());
+    }
+
+    #[test]
+    fn lib_extension_for_follows_the_os_and_linking_mode() {
+        assert_eq!(lib_extension_for("linux", &LinkingMode::Static), "a");
+        assert_eq!(lib_extension_for("linux", &LinkingMode::Dynamic), "so");
+        assert_eq!(lib_extension_for("macos", &LinkingMode::Dynamic), "dylib");
+        assert_eq!(lib_extension_for("windows", &LinkingMode::Static), "lib");
+        assert_eq!(lib_extension_for("mingw", &LinkingMode::Dynamic), "dll");
+        assert_eq!(lib_extension_for("wasm32", &LinkingMode::Static), "wasm");
+    }
+
+    #[test]
+    fn override_extension_only_applies_to_its_os_and_linking_mode() {
+        let mut build = C3FFI::new();
+        build.override_extension("linux", LinkingMode::Dynamic, "so.1");
+
+        assert_eq!(build.lib_extension("linux"), "a");
+        assert_eq!(build.lib_extension("freebsd"), "a");
+        build.linking_mode(LinkingMode::Dynamic);
+        assert_eq!(build.lib_extension("linux"), "so.1");
+        assert_eq!(build.lib_extension("freebsd"), "so");
+    }
+
+    #[test]
+    fn stack_size_is_passed_to_the_linker_for_dynamic_elf_libraries() {
+        let dir = temp_dir("stack-size");
+        let mut build = builder(&dir);
+        build.stack_size(1 << 20);
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("-zstack-size")));
+
+        build.linking_mode(LinkingMode::Dynamic);
+        assert!(contains_args(&args(&build), &["-z", "-zstack-size=1048576"]));
+
+        build.c3_target("macos-aarch64");
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("-zstack-size")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn attempt_compilation_describes_what_was_built() {
+        let dir = temp_dir("compile-result");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(
+                &dir,
+                r#"touch "$out_dir/thing.h"; mkdir -p "$obj_dir" && touch "$obj_dir/thing.o"
+echo "(thing.c3:1:1) Warning: unused" >&2"#,
+            ))
+            .file(&file);
+
+        let result = build.attempt_compilation("thing").unwrap();
+        assert_eq!(result.library, dir.path().join("libthing.a"));
+        assert_eq!(result.libraries, [dir.path().join("libthing.a")]);
+        assert_eq!(result.headers, [dir.path().join("thing.h")]);
+        assert_eq!(result.objects, [dir.path().join("obj").join("thing").join("thing.o")]);
+        assert_eq!(result.warnings, 1);
+        assert_eq!(result.import_library, None);
+        assert!(!result.skipped);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn headers_only_lists_what_c3c_generated_for_the_library() {
+        let dir = temp_dir("headers");
+        let file = source(&dir, "thing.c3", "module thing;");
+        source(&dir, "unrelated.h", "int unrelated(void);");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, r#"touch "$out_dir/thing.h""#)).cache(true).file(&file);
+
+        assert_eq!(build.attempt_compilation("thing").unwrap().headers, [dir.path().join("thing.h")]);
+        let cached = build.attempt_compilation("thing").unwrap();
+        assert_eq!(recorded_args(&dir).len(), 1);
+        assert_eq!(cached.headers, [dir.path().join("thing.h")]);
+    }
+
+    #[test]
+    fn reproducible_uses_manifest_relative_paths_and_a_fixed_build_dir() {
+        let dir = temp_dir("reproducible");
+        let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("src").join("lib.rs");
+        let mut build = builder(&dir);
+        build.file(&file);
+        let build_dir = dir.path().join("build").display().to_string();
+        assert!(args(&build).contains(&file.display().to_string()));
+        assert!(!args(&build).contains(&"--build-dir".to_string()));
+
+        build.reproducible(true);
+        let args = args(&build);
+        assert!(args.contains(&Path::new("src").join("lib.rs").display().to_string()));
+        assert!(contains_args(&args, &["--build-dir", &build_dir]));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn reproducible_sets_the_environment_for_c3c() {
+        let dir = temp_dir("reproducible-env");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let env_file = dir.path().join("env");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, &format!("echo \"$ZERO_AR_DATE\" > '{}'", env_file.display())))
+            .reproducible(true)
+            .file(&file);
+
+        build.attempt_compilation("thing").unwrap();
+        assert_eq!(fs::read_to_string(env_file).unwrap(), "1\n");
+    }
+
+    #[test]
+    fn keep_symbols_are_passed_to_the_linker_of_each_target() {
+        let dir = temp_dir("keep-symbols");
+        let mut build = builder(&dir);
+        build.linking_mode(LinkingMode::Dynamic).keep_symbols(&["entry"]);
+        assert!(contains_args(&args(&build), &["-z", "--undefined=entry"]));
+
+        build.c3_target("macos-aarch64");
+        assert!(contains_args(&args(&build), &["-z", "-u", "-z", "_entry"]));
+
+        build.c3_target("windows-x64");
+        assert!(contains_args(&args(&build), &["-z", "/INCLUDE:entry"]));
+
+        build.c3_target("wasm32").linking_mode(LinkingMode::Static);
+        assert!(contains_args(&args(&build), &["-z", "--export=entry"]));
+    }
+
+    #[test]
+    fn c3_error_describes_the_failure() {
+        assert_eq!(
+            C3Error::MissingSourceFile(PathBuf::from("extern/thing.c3")).to_string(),
+            "source file extern/thing.c3 does not exist"
+        );
+        assert_eq!(
+            C3Error::CompilationFailed {
+                code: Some(1),
+                stderr: "Error: oops\n".to_string(),
+                format: ErrorFormat::Plain,
+            }
+            .to_string(),
+            "c3c exited with code 1:\nError: oops"
+        );
+        assert!(C3Error::Io(io::Error::other("disk full")).source().is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "handled: source file")]
+    fn compile_hands_errors_to_on_error() {
+        let dir = temp_dir("on-error");
+        let mut build = builder(&dir);
+        build
+            .on_error(|err| format!("handled: {}", err))
+            .file(dir.path().join("missing.c3"))
+            .compile("thing");
+    }
+
+    #[test]
+    fn c_prototype_to_rust_translates_primitive_signatures() {
+        assert_eq!(
+            c_prototype_to_rust("extern int32_t add(int32_t a, int32_t b);").unwrap(),
+            "pub fn add(a: i32, b: i32) -> i32;"
+        );
+        assert_eq!(c_prototype_to_rust("void reset(void);").unwrap(), "pub fn reset();");
+        assert_eq!(
+            c_prototype_to_rust("const char* describe(size_t type, double* out);").unwrap(),
+            "pub fn describe(type_: usize, out: *mut f64) -> *const ::core::ffi::c_char;"
+        );
+        assert_eq!(
+            c_prototype_to_rust("void unnamed(int32_t, unsigned int);").unwrap(),
+            "pub fn unnamed(arg0: i32, arg1: u32);"
+        );
+        assert_eq!(
+            c_prototype_to_rust("struct Thing* thing_new(void);").unwrap(),
+            "pub fn thing_new() -> *mut ::core::ffi::c_void;"
+        );
+        assert_eq!(c_prototype_to_rust("int log(const char* format, ...);"), None);
+        assert_eq!(c_prototype_to_rust("Thing make(void);"), None);
+    }
+
+    #[test]
+    fn c_header_to_rust_skips_what_it_cannot_translate() {
+        let header =
+            "#pragma once\ntypedef struct Thing Thing;\nint32_t add(int32_t a, int32_t b);\nThing make(void);\n";
+        assert_eq!(
+            c_header_to_rust(Path::new("thing.h"), header),
+            "// Generated by c3ne from thing.h.\nunsafe extern \"C\" {\n    pub fn add(a: i32, b: i32) -> i32;\n    \
+             // Skipped, unsupported signature: Thing make(void);\n}\n"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn generate_rust_bindings_only_translates_the_headers_of_the_library() {
+        let dir = temp_dir("rust-bindings");
+        let file = source(&dir, "thing.c3", "module thing;");
+        source(&dir, "unrelated.h", "int32_t unrelated(void);");
+        let bindings = dir.path().join("thing.rs");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, r#"echo 'int32_t thing_add(int32_t a, int32_t b);' > "$out_dir/thing.h""#))
+            .generate_rust_bindings(&bindings)
+            .file(&file);
+
+        build.attempt_compilation("thing").unwrap();
+        let bindings = fs::read_to_string(&bindings).unwrap();
+        assert!(bindings.contains("pub fn thing_add(a: i32, b: i32) -> i32;"));
+        assert!(!bindings.contains("unrelated"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn max_forwarded_warnings_does_not_change_the_warning_count() {
+        let dir = temp_dir("forward-warnings");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, "printf 'Warning: a\\nWarning: b\\nWarning: c\\n' >&2"))
+            .forward_warnings(true)
+            .max_forwarded_warnings(1)
+            .file(&file);
+
+        assert_eq!(build.attempt_compilation("thing").unwrap().warnings, 3);
+    }
+
+    #[test]
+    fn check_only_runs_compile_only_without_linking() {
+        let dir = temp_dir("check-only");
+        let mut build = builder(&dir);
+        assert_eq!(args(&build)[0], "static-lib");
+        assert!(!args(&build).contains(&"-C".to_string()));
+
+        build.check_only(true);
+        assert_eq!(args(&build)[0], "compile-only");
+        assert!(args(&build).contains(&"-C".to_string()));
+
+        build.check_subcommand("compile");
+        assert_eq!(args(&build)[0], "compile");
+    }
+
+    #[test]
+    fn project_builds_the_selected_config_without_listing_files() {
+        let dir = temp_dir("project");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.file(&file).project(dir.path()).build_config("thing-release");
+
+        let args = args(&build);
+        assert_eq!(args[..2], ["build", "thing-release"]);
+        assert!(!args.contains(&file.display().to_string()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn build_config_requires_a_project() {
+        let dir = temp_dir("build-config");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).build_config("thing-release").file(&file);
+
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::InvalidConfiguration(message)) if message.contains("build_config")
+        ));
+    }
+
+    #[test]
+    fn c3_module_name_finds_the_first_module_declaration() {
+        assert_eq!(c3_module_name("// thing\nmodule foo::bar @private;\nmodule baz;").unwrap(), "foo::bar");
+        assert_eq!(c3_module_name("  module thing;").unwrap(), "thing");
+        assert_eq!(c3_module_name("modules are great;\nimport std::io;"), None);
+    }
+
+    #[test]
+    fn object_map_pairs_sources_with_the_objects_of_their_modules() {
+        let dir = temp_dir("object-map");
+        let bar = source(&dir, "bar.c3", "module foo::bar;");
+        let baz = source(&dir, "baz.c3", "module baz;");
+        let unnamed = source(&dir, "unnamed.c3", "fn void main() {}");
+        let mut build = builder(&dir);
+        build.files([&bar, &baz, &unnamed]);
+
+        let result = CompileResult {
+            objects: vec![dir.path().join("foo.bar.o"), dir.path().join("baz.o")],
+            ..CompileResult::default()
+        };
+        let object_map = build.object_map(&result);
+        assert_eq!(object_map.len(), 2);
+        assert_eq!(object_map[&bar], dir.path().join("foo.bar.o"));
+        assert_eq!(object_map[&baz], dir.path().join("baz.o"));
+    }
+
+    #[test]
+    fn parse_env_value_handles_quotes_escapes_and_comments() {
+        assert_eq!(parse_env_value("plain"), "plain");
+        assert_eq!(parse_env_value("plain # comment"), "plain");
+        assert_eq!(parse_env_value("a#b"), "a#b");
+        assert_eq!(parse_env_value("'single \\n # kept' # comment"), "single \\n # kept");
+        assert_eq!(parse_env_value(r#""double\n\"quoted\" \\" # comment"#), "double\n\"quoted\" \\");
+        assert_eq!(parse_env_value(""), "");
+    }
+
+    #[test]
+    fn env_file_sets_each_variable_once() {
+        let dir = temp_dir("env-file");
+        let env_file = source(&dir, ".env", "# settings\n\nexport A=1\nB = 'two'\nA=3\n");
+        let mut build = builder(&dir);
+        build.case_insensitive_env(false).env_file(&env_file).unwrap();
+        assert_eq!(
+            build.environment_variables,
+            [("B".to_string(), "two".to_string()), ("A".to_string(), "3".to_string())]
+        );
+
+        let env_file = source(&dir, "broken.env", "A=1\nB\n");
+        assert!(matches!(
+            build.env_file(&env_file),
+            Err(C3Error::InvalidConfiguration(message)) if message.ends_with("broken.env:2: expected KEY=VALUE")
+        ));
+    }
+
+    #[test]
+    fn optional_skips_an_invalid_configuration() {
+        let dir = temp_dir("optional-invalid");
+        let mut build = builder(&dir);
+        build.optional(true).file(dir.path().join("missing.c3"));
+
+        assert!(build.attempt_compilation("thing").unwrap().skipped);
+        assert!(build.link_directives.is_empty());
+    }
+
+    /// Runs the test with the given name in a child process with the given environment variables set, returning what it
+    /// printed, for tests of the directives printed to cargo or of code reading the environment cargo provides.
+    fn child_stdout(test: &str, envs: &[(&str, &str)]) -> String {
+        let output = Command::new(env::current_exe().unwrap())
+            .args([&format!("tests::{}", test), "--exact", "--nocapture", "--quiet"])
+            .envs(envs.iter().copied())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn emit_cfg_on_success_is_only_printed_after_a_successful_build() {
+        let succeeded = child_stdout("emit_cfg_on_success_child", &[("C3NE_TEST_SCRIPT", "")]);
+        assert!(succeeded.lines().any(|line| line == "cargo::rustc-cfg=c3_available"));
+        assert!(!succeeded.lines().any(|line| line == "cargo::rustc-cfg=c3_unavailable"));
+
+        let failed = child_stdout("emit_cfg_on_success_child", &[("C3NE_TEST_SCRIPT", "exit 1")]);
+        assert!(!failed.lines().any(|line| line == "cargo::rustc-cfg=c3_available"));
+        assert!(failed.lines().any(|line| line == "cargo::rustc-cfg=c3_unavailable"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn emit_cfg_on_success_child() {
+        let Ok(script) = env::var("C3NE_TEST_SCRIPT") else {
+            return;
+        };
+
+        let dir = temp_dir("emit-cfg-on-success");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, &script))
+            .optional(true)
+            .emit_cfg_on_success("c3_available")
+            .file(&file);
+        build.attempt_compilation("thing").unwrap();
+    }
+
+    #[test]
+    fn override_arch_replaces_the_translation_of_an_architecture() {
+        let mut build = C3FFI::new();
+        build.override_arch("x86_64", "amd64").override_arch("mips", "mips32").override_arch("mips", "mips");
+        assert_eq!(
+            build.architecture_overrides,
+            [("x86_64".to_string(), "amd64".to_string()), ("mips".to_string(), "mips".to_string())]
+        );
+
+        let overrides = &build.architecture_overrides;
+        assert_eq!(translate_target("x86_64-unknown-linux-gnu", overrides).unwrap(), "linux-amd64");
+        assert_eq!(translate_target("mips-unknown-linux-gnu", overrides).unwrap(), "linux-mips");
+        assert_eq!(translate_target("aarch64-unknown-linux-gnu", overrides).unwrap(), "linux-aarch64");
+        assert!(matches!(
+            translate_target("mips-unknown-linux-gnu", &[]),
+            Err(C3Error::UnsupportedTarget(target)) if target == "mips-unknown-linux-gnu"
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn source_bytes_are_written_out_and_compiled() {
+        let dir = temp_dir("source-bytes");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, ""))
+            .source_bytes("generated.c3", b"module old;")
+            .source_bytes("generated.c3", b"module generated;");
+
+        let generated = dir.path().join("c3-sources").join("generated.c3");
+        assert!(args(&build).contains(&generated.display().to_string()));
+
+        build.attempt_compilation("thing").unwrap();
+        assert_eq!(fs::read_to_string(&generated).unwrap(), "module generated;");
+    }
+
+    #[test]
+    fn prepended_lib_dirs_are_searched_first() {
+        let dir = temp_dir("prepend-lib-dirs");
+        let mut build = builder(&dir);
+        build
+            .c3_lib_dirs(["libs", "vendor"])
+            .prepend_c3_lib_dir("vendor")
+            .prepend_c3_lib_dir("patched")
+            .compiled_lib_dir("native")
+            .prepend_compiled_lib_dir("patched-native");
+
+        let args = args(&build);
+        assert!(contains_args(&args, &["--libdir", "patched", "--libdir", "vendor", "--libdir", "libs"]));
+        assert!(contains_args(&args, &["-L", "patched-native", "-L", "native"]));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn require_stdlib_version_checks_the_version_c3c_reports() {
+        let dir = temp_dir("stdlib-version");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(
+                &dir,
+                r#"[ "$command" = --version ] && printf 'C3 Compiler Version: 0.7.6\nStdlib: 0.7.5\n' && exit 0"#,
+            ))
+            .file(&file);
+
+        build.require_stdlib_version("0.7");
+        assert!(build.attempt_compilation("thing").is_ok());
+        build.require_stdlib_version("0.7.5");
+        assert!(build.attempt_compilation("thing").is_ok());
+        build.require_stdlib_version("0.7.6");
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::StdlibVersionMismatch { found, required }) if found == "0.7.5" && required == "0.7.6"
+        ));
+
+        fake_c3c(&dir, r#"[ "$command" = --version ] && echo 'C3 Compiler Version: 0.7.6' && exit 0"#);
+        assert!(build.attempt_compilation("thing").is_ok());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn split_output_builds_balanced_groups_in_order() {
+        let dir = temp_dir("split-output");
+        let files: Vec<PathBuf> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|name| source(&dir, &format!("{}.c3", name), &format!("module {};", name)))
+            .collect();
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).split_output(3).files(&files);
+
+        let result = build.attempt_compilation("thing").unwrap();
+        assert_eq!(
+            result.libraries,
+            ["libthing_0.a", "libthing_1.a", "libthing_2.a"].map(|library| dir.path().join(library))
+        );
+        assert_eq!(result.library, dir.path().join("libthing_0.a"));
+        assert_eq!(
+            build.link_directives.iter().filter(|directive| directive.starts_with("cargo::rustc-link-lib=")).count(),
+            3
+        );
+
+        let runs = recorded_args(&dir);
+        let groups: Vec<&[PathBuf]> = vec![&files[..2], &files[2..4], &files[4..]];
+        assert_eq!(runs.len(), groups.len());
+        for (run, group) in runs.iter().zip(groups) {
+            let compiled: Vec<PathBuf> = run.iter().filter(|arg| arg.ends_with(".c3")).map(PathBuf::from).collect();
+            assert_eq!(compiled, group);
+        }
+
+        build.split_output(0);
+        assert!(matches!(build.attempt_compilation("thing"), Err(C3Error::InvalidConfiguration(_))));
+    }
+
+    #[test]
+    fn link_sysroot_is_passed_to_the_linker_of_dynamic_libraries() {
+        let dir = temp_dir("link-sysroot");
+        let mut build = builder(&dir);
+        build.link_sysroot("/opt/sysroot");
+        assert!(!args(&build).iter().any(|arg| arg.contains("/opt/sysroot")));
+
+        build.linking_mode(LinkingMode::Dynamic);
+        assert!(contains_args(&args(&build), &["-z", "--sysroot=/opt/sysroot"]));
+
+        build.c3_target("macos-aarch64");
+        assert!(contains_args(&args(&build), &["-z", "-syslibroot", "-z", "/opt/sysroot"]));
+
+        build.c3_target("windows-x64");
+        assert!(!args(&build).iter().any(|arg| arg.contains("/opt/sysroot")));
+    }
+
+    #[test]
+    fn is_cross_compiling_compares_host_and_target() {
+        let host = "x86_64-unknown-linux-gnu";
+        let stdout = |target| {
+            child_stdout(
+                "is_cross_compiling_child",
+                &[("C3NE_TEST_CHILD", "1"), ("HOST", host), ("TARGET", target)],
+            )
+        };
+        assert!(stdout(host).contains("cross compiling: false"));
+        assert!(stdout("aarch64-linux-android").contains("cross compiling: true"));
+    }
+
+    #[test]
+    fn is_cross_compiling_child() {
+        if env::var_os("C3NE_TEST_CHILD").is_some() {
+            println!("cross compiling: {}", C3FFI::new().is_cross_compiling().unwrap());
+        }
+    }
+
+    #[test]
+    fn diagnostics_are_classified_by_their_prefix() {
+        let build = C3FFI::new();
+        assert_eq!(build.classify_diagnostic("(a.c3:1:2) Error: oops"), DiagnosticSeverity::Error);
+        assert_eq!(build.classify_diagnostic("(a.c3:1:2) Warning: unused"), DiagnosticSeverity::Warning);
+        assert_eq!(build.classify_diagnostic("Note: declared here"), DiagnosticSeverity::Note);
+        assert_eq!(build.classify_diagnostic("Program linked to static library"), DiagnosticSeverity::None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn diagnostic_classifier_decides_what_counts_as_a_warning() {
+        let dir = temp_dir("diagnostic-classifier");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, "printf 'Warning: a\\nlint: b\\nlint: c\\n' >&2"))
+            .diagnostic_classifier(|line| {
+                if line.starts_with("lint:") {
+                    DiagnosticSeverity::Warning
+                } else {
+                    DiagnosticSeverity::None
+                }
+            })
+            .file(&file);
+
+        assert_eq!(build.classify_diagnostic("Warning: a"), DiagnosticSeverity::None);
+        assert_eq!(build.attempt_compilation("thing").unwrap().warnings, 2);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn write_build_manifest_records_the_build() {
+        let dir = temp_dir("build-manifest");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let manifest_path = dir.path().join("manifest.json");
+        let mut build = builder(&dir);
+        let compiler = fake_c3c(&dir, r#"[ "$command" = --version ] && echo 'C3 Compiler Version: 0.7.6' && exit 0"#);
+        build.compiler(&compiler).write_build_manifest(&manifest_path).file(&file);
+        build.attempt_compilation("thing").unwrap();
+
+        let manifest = fs::read_to_string(&manifest_path).unwrap();
+        assert!(manifest.contains(&format!("\"compiler\": {},", json_string(&compiler))));
+        assert!(manifest.contains("\"compiler_version\": \"0.7.6\","));
+        assert!(manifest.contains("\"target\": \"linux-x64\","));
+        assert!(manifest.contains("\"args\": [\"static-lib\", "));
+        assert!(manifest.contains(&format!(
+            "\"inputs\": [{{\"path\": {}, \"fnv1a64\": \"{:016x}\"}}],",
+            json_string(&file.display().to_string()),
+            content_hash(b"module thing;")
+        )));
+        assert!(manifest.contains(&format!(
+            "\"output\": {{\"path\": {}, \"fnv1a64\": \"cbf29ce484222325\"}}",
+            json_string(&dir.path().join("libthing.a").display().to_string())
+        )));
+        assert!(build.generated_files().contains(&manifest_path));
+    }
+
+    #[test]
+    fn panic_strategy_selects_whether_panics_print_a_message() {
+        let dir = temp_dir("panic-strategy");
+        let mut build = builder(&dir);
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("--panic-msg")));
+
+        build.panic_strategy(PanicStrategy::Unwind);
+        assert!(args(&build).contains(&"--panic-msg=yes".to_string()));
+
+        build.panic_strategy(PanicStrategy::Abort);
+        assert!(args(&build).contains(&"--panic-msg=no".to_string()));
+
+        build.panic_strategy(PanicStrategy::Default);
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("--panic-msg")));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn verify_link_reports_undefined_symbols() {
+        let dir = temp_dir("verify-link");
+        let archive = |name: &str, code: &str| {
+            let object = dir.path().join(format!("{}.o", name));
+            let library = dir.path().join(format!("lib{}.a", name));
+            let status = Command::new("cc")
+                .args(["-x", "c", "-c", "-fPIC", "-o"])
+                .arg(&object)
+                .arg(source(&dir, &format!("{}.c", name), code))
+                .status()
+                .unwrap();
+            assert!(status.success());
+            assert!(Command::new("ar").arg("rcs").arg(&library).arg(&object).status().unwrap().success());
+            library
+        };
+        let out_dir = dir.path().to_str().unwrap();
+        let build = builder(&dir);
+
+        let linked = build.link_check(&archive("good", "int good(void) { return 1; }"), "linux", out_dir).unwrap();
+        assert_eq!(linked, dir.path().join("verify-link").join("libgood.a"));
+
+        let broken = archive("broken", "int missing(void);\nint broken(void) { return missing(); }");
+        assert!(matches!(
+            build.link_check(&broken, "linux", out_dir),
+            Err(C3Error::UndefinedSymbols(symbols)) if symbols == ["missing"]
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn memory_limit_applies_to_c3c() {
+        let dir = temp_dir("memory-limit");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, r#"ulimit -v > "$out_dir/limit""#))
+            .memory_limit(1 << 30)
+            .file(&file);
+        build.attempt_compilation("thing").unwrap();
+
+        assert_eq!(fs::read_to_string(dir.path().join("limit")).unwrap().trim(), (1 << 20).to_string());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn phase_env_is_only_set_for_its_phase() {
+        let dir = temp_dir("phase-env");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let env_log = dir.path().join("env");
+        let script = format!(
+            r#"echo "$command ${{SCOPED:-unset}} $SHARED" >> '{}'
+[ "$command" = --version ] && echo 'C3 Compiler Version: 0.7.6' && exit 0"#,
+            env_log.display()
+        );
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, &script))
+            .require_min_version("0.7")
+            .environment_variable(("SHARED", "everywhere"))
+            .phase_env(Phase::Compile, "SCOPED", "old")
+            .phase_env(Phase::Compile, "SCOPED", "compile")
+            .phase_env(Phase::Compile, "SHARED", "compile")
+            .file(&file);
+        build.attempt_compilation("thing").unwrap();
+
+        assert_eq!(fs::read_to_string(&env_log).unwrap(), "--version unset everywhere\nstatic-lib compile compile\n");
+    }
+
+    #[test]
+    fn validate_rejects_features_that_are_not_bare_names() {
+        for feature in ["", "-D FOO", "--foo", "FOO BAR"] {
+            let mut build = C3FFI::new();
+            build.feature(feature);
+            assert!(matches!(build.validate(), Err(C3Error::InvalidConfiguration(_))), "{:?}", feature);
+        }
+
+        let mut build = C3FFI::new();
+        build.features(["FOO", "BAR_2"]).arg("-O3").subcommand("static-lib");
+        assert!(build.validate().is_ok());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_failed_compilation_leaves_the_previous_library_in_place() {
+        let dir = temp_dir("staging");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let library = dir.path().join("libthing.a");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).file(&file);
+        build.attempt_compilation("thing").unwrap();
+        fs::write(&library, "previous").unwrap();
+
+        fake_c3c(&dir, r#"echo partial > "$out_dir/$out_name.a" && echo partial > "$out_dir/thing.h" && exit 1"#);
+        assert!(build.attempt_compilation("thing").is_err());
+        assert_eq!(fs::read_to_string(&library).unwrap(), "previous");
+        assert!(!dir.path().join("thing.h").exists());
+        assert!(!dir.path().join("staging").join("thing").exists());
+    }
+
+    #[test]
+    fn treat_as_c3_stages_files_under_a_c3_name() {
+        let dir = temp_dir("treat-as-c3");
+        let generated = source(&dir, "thing.c3gen", "module thing;");
+        let mut build = builder(&dir);
+        build.treat_as_c3(".c3gen").treat_as_c3("c3gen").file(&generated);
+        assert_eq!(build.c3_extensions, ["c3gen"]);
+
+        let out_dir = Path::new("out");
+        let staged = |file: &str| build.staged_source_path("out", Path::new(file)).unwrap();
+        assert_eq!(staged("gen/thing.c3gen").unwrap(), out_dir.join("c3-sources/gen/thing.c3gen.c3"));
+        assert_eq!(staged("../thing.c3gen").unwrap(), out_dir.join("c3-sources/__/thing.c3gen.c3"));
+        assert_eq!(staged("gen/thing.c3"), None);
+
+        let staged = build.staged_source_path(dir.path().to_str().unwrap(), &generated).unwrap().unwrap();
+        assert!(args(&build).contains(&staged.display().to_string()));
+        assert!(!args(&build).contains(&generated.display().to_string()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn generated_files_lists_everything_the_compilation_created() {
+        let dir = temp_dir("generated-files");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, r#"touch "$out_dir/thing.h"; mkdir -p "$obj_dir" && touch "$obj_dir/thing.o""#))
+            .source_bytes("generated.c3", b"module generated;")
+            .file(&file);
+        build.attempt_compilation("thing").unwrap();
+
+        let generated_files = build.generated_files();
+        for expected in [
+            dir.path().join("libthing.a"),
+            dir.path().join("thing.h"),
+            dir.path().join("obj").join("thing").join("thing.o"),
+            dir.path().join("c3-sources").join("generated.c3"),
+        ] {
+            assert!(generated_files.contains(&expected), "{} in {:?}", expected.display(), generated_files);
+        }
+        assert!(generated_files.iter().all(|file| file.exists()));
+    }
+
+    #[test]
+    fn libc_selects_the_flag_passed_to_c3c() {
+        let dir = temp_dir("libc");
+        let mut build = builder(&dir);
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("--linux-libc")));
+
+        build.libc(LibcKind::Glibc);
+        assert!(args(&build).contains(&"--linux-libc=gnu".to_string()));
+        build.libc(LibcKind::Musl);
+        assert!(args(&build).contains(&"--linux-libc=musl".to_string()));
+        build.libc(LibcKind::Newlib);
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("--linux-libc")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn libc_is_linked_and_must_match_the_target() {
+        let stdout = |target| child_stdout("libc_child", &[("C3NE_TEST_CHILD", "1"), ("TARGET", target)]);
+        let musl = stdout("x86_64-unknown-linux-musl");
+        assert!(musl.lines().any(|line| line == "cargo::rustc-link-lib=static:-bundle=c"));
+        assert!(musl.contains("libc: Ok"));
+
+        let gnu = stdout("x86_64-unknown-linux-gnu");
+        assert!(!gnu.lines().any(|line| line == "cargo::rustc-link-lib=static:-bundle=c"));
+        assert!(gnu.contains("libc Musl cannot be used with target x86_64-unknown-linux-gnu"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn libc_child() {
+        if env::var_os("C3NE_TEST_CHILD").is_none() {
+            return;
+        }
+
+        let dir = temp_dir("libc-child");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).libc(LibcKind::Musl).file(&file);
+        println!("libc: {:?}", build.attempt_compilation("thing").map(|_| ()));
+    }
+
+    #[test]
+    fn codegen_seed_makes_the_build_reproducible_and_changes_the_config_hash() {
+        let dir = temp_dir("codegen-seed");
+        let mut build = builder(&dir);
+        build.reproducible(true);
+        let unseeded = build.config_hash("thing", "linux-x64").unwrap();
+
+        build.reproducible(false).codegen_seed(1);
+        assert!(build.reproducible);
+        assert!(args(&build).contains(&"--build-dir".to_string()));
+        let seeded = build.config_hash("thing", "linux-x64").unwrap();
+        build.codegen_seed(2);
+        let reseeded = build.config_hash("thing", "linux-x64").unwrap();
+        assert_ne!(unseeded, seeded);
+        assert_ne!(seeded, reseeded);
+    }
+
+    #[test]
+    fn link_dead_code_keeps_unused_functions() {
+        let dir = temp_dir("link-dead-code");
+        let mut build = builder(&dir);
+        assert!(!args(&build).contains(&"--no-strip-unused".to_string()));
+
+        build.link_dead_code(true);
+        assert!(args(&build).contains(&"--no-strip-unused".to_string()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn windows_dlls_are_linked_through_their_import_library() {
+        let dir = temp_dir("import-library");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, r#"touch "$out_dir/$out_name.dll" "$out_dir/$out_name.lib""#))
+            .c3_target("windows-x64")
+            .linking_mode(LinkingMode::Dynamic)
+            .file(&file);
+
+        let result = build.attempt_compilation("thing").unwrap();
+        assert_eq!(result.library, dir.path().join("libthing.dll"));
+        assert_eq!(result.import_library, Some(dir.path().join("libthing.lib")));
+        assert!(build.link_directives.contains(&"cargo::rustc-link-lib=dylib=libthing".to_string()));
+    }
+
+    #[test]
+    fn relative_path_walks_up_to_the_common_ancestor() {
+        let relative = |from: &str, to: &str| relative_path(Path::new(from), Path::new(to));
+        assert_eq!(relative("/work/crate", "/work/crate/target/out").unwrap(), Path::new("target/out"));
+        assert_eq!(relative("/work/crate", "/work/target/out").unwrap(), Path::new("../target/out"));
+        assert_eq!(relative("/work/crate", "/work/crate").unwrap(), Path::new(""));
+        assert_eq!(relative("work/crate", "/target"), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn relative_link_search_links_from_the_manifest_dir() {
+        let dir = temp_dir("relative-link-search");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).relative_link_search(true).file(&file);
+        build.attempt_compilation("thing").unwrap();
+
+        let link_search = relative_path(Path::new(env!("CARGO_MANIFEST_DIR")), dir.path()).unwrap();
+        assert!(link_search.is_relative());
+        assert_eq!(build.link_directives[0], format!("cargo::rustc-link-search=native={}", link_search.display()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn prebuild_commands_run_over_the_sources_before_compiling() {
+        let dir = temp_dir("prebuild-command");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let log = dir.path().join("prebuild");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, ""))
+            .prebuild_command("sh", &["-c", &format!("echo \"$@\" > '{}'", log.display()), "lint"])
+            .file(&file);
+        build.attempt_compilation("thing").unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap().trim(), file.display().to_string());
+
+        fs::remove_file(dir.path().join("c3c.args")).unwrap();
+        build.prebuild_command("sh", &["-c", "echo badly formatted; exit 3", "lint"]);
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::PrebuildFailed { program, code: Some(3), output })
+                if program == "sh" && output == "badly formatted\n"
+        ));
+        assert!(!dir.path().join("c3c.args").exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn create_output_dir_decides_whether_a_missing_out_dir_is_created() {
+        let dir = temp_dir("create-output-dir");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let out_dir = dir.path().join("nested").join("out");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).create_output_dir(false).file(&file);
+        build.out_dir = Some(out_dir.clone());
+        assert!(matches!(build.attempt_compilation("thing"), Err(C3Error::InvalidConfiguration(_))));
+        assert!(!out_dir.exists());
+
+        build.create_output_dir(true);
+        build.attempt_compilation("thing").unwrap();
+        assert!(out_dir.join("libthing.a").is_file());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn optional_skips_a_failed_compilation() {
+        let dir = temp_dir("optional-failed");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "exit 1")).file(&file);
+        assert!(matches!(build.attempt_compilation("thing"), Err(C3Error::CompilationFailed { .. })));
+
+        build.optional(true);
+        assert!(build.attempt_compilation("thing").unwrap().skipped);
+        assert!(build.link_directives.is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_failing_compiler_is_reported_with_its_exit_code_and_stderr() {
+        let dir = temp_dir("compilation-failed");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, "echo '(thing.c3:1:1) Error: oops' >&2; exit 2"))
+            .file(&file);
+
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::CompilationFailed { code: Some(2), stderr, .. }) if stderr.contains("Error: oops")
+        ));
+        assert!(!dir.path().join("libthing.a").exists());
+    }
+
+    #[test]
+    #[cfg(all(feature = "smoke-test", target_os = "linux"))]
+    fn smoke_test_loads_the_library_and_finds_its_symbols() {
+        let dir = temp_dir("smoke-test");
+        let library = dir.path().join("libthing.so");
+        let status = Command::new("cc")
+            .args(["-x", "c", "-shared", "-fPIC", "-o"])
+            .arg(&library)
+            .arg(source(&dir, "thing.c", "int thing_add(int a, int b) { return a + b; }"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut build = builder(&dir);
+        build.smoke_test("thing_add");
+        build.smoke_test_library(&library).unwrap();
+
+        build.smoke_test("thing_sub");
+        assert!(matches!(
+            build.smoke_test_library(&library),
+            Err(C3Error::SmokeTestFailed { symbol: Some(symbol), .. }) if symbol == "thing_sub"
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn the_link_directive_follows_the_linking_mode() {
+        let dir = temp_dir("link-directive");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).file(&file);
+
+        build.attempt_compilation("thing").unwrap();
+        assert_eq!(
+            build.link_directives,
+            [
+                format!("cargo::rustc-link-search=native={}", dir.path().display()),
+                "cargo::rustc-link-lib=static=thing".to_string(),
+            ]
+        );
+
+        build.linking_mode(LinkingMode::Dynamic).attempt_compilation("thing").unwrap();
+        assert_eq!(build.link_directives[1], "cargo::rustc-link-lib=dylib=thing");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn compile_with_output_returns_what_c3c_printed() {
+        let dir = temp_dir("compile-with-output");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, "echo 'Program linked to static library'; echo 'Warning: unused' >&2"))
+            .file(&file);
+
+        let output = build.compile_with_output("thing").unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"Program linked to static library\n");
+        assert_eq!(output.stderr, b"Warning: unused\n");
+
+        fake_c3c(&dir, "echo 'Error: oops' >&2; exit 1");
+        let output = build.optional(true).compile_with_output("thing").unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(output.stderr, b"Error: oops\n");
+
+        fs::remove_file(&file).unwrap();
+        let output = build.compile_with_output("thing").unwrap();
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty() && output.stderr.is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn same_named_sources_in_different_directories_are_staged_apart() {
+        let dir = temp_dir("staged-paths");
+        fs::create_dir_all(dir.path().join("a")).unwrap();
+        fs::create_dir_all(dir.path().join("b")).unwrap();
+        let a = source(&dir, "a/thing.c3gen", "module a::thing;");
+        let b = source(&dir, "b/thing.c3gen", "module b::thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).treat_as_c3("c3gen").files([&a, &b]);
+        build.attempt_compilation("thing").unwrap();
+
+        let out_dir = dir.path().to_str().unwrap();
+        let staged_a = build.staged_source_path(out_dir, &a).unwrap().unwrap();
+        let staged_b = build.staged_source_path(out_dir, &b).unwrap().unwrap();
+        assert_ne!(staged_a, staged_b);
+        assert_eq!(fs::read_to_string(&staged_a).unwrap(), "module a::thing;");
+        assert_eq!(fs::read_to_string(&staged_b).unwrap(), "module b::thing;");
+
+        let args = &recorded_args(&dir)[0];
+        assert!(args.contains(&staged_a.display().to_string()) && args.contains(&staged_b.display().to_string()));
+    }
+
+    #[test]
+    fn verbose_makes_c3c_verbose() {
+        let dir = temp_dir("verbose");
+        let mut build = builder(&dir);
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("-v")));
+
+        build.verbose(true);
+        assert!(args(&build).contains(&"-v".to_string()));
+    }
+
+    #[test]
+    fn single_module_is_only_passed_when_set() {
+        let dir = temp_dir("single-module");
+        let mut build = builder(&dir);
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("--single-module")));
+
+        build.single_module(true);
+        assert!(args(&build).contains(&"--single-module=yes".to_string()));
+        build.single_module(false);
+        assert!(args(&build).contains(&"--single-module=no".to_string()));
+    }
+
+    #[test]
+    fn typed_alternative_names_the_method_covering_a_raw_argument() {
+        assert_eq!(typed_alternative("-O3"), Some("optimization_level"));
+        assert_eq!(typed_alternative("-DFOO"), Some("feature"));
+        assert_eq!(typed_alternative("-lm"), Some("compiled_lib"));
+        assert_eq!(typed_alternative("--reloc=pic"), Some("reloc_model"));
+        assert_eq!(typed_alternative("dynamic-lib"), Some("linking_mode"));
+        assert_eq!(typed_alternative("compile-only"), Some("check_only"));
+        assert_eq!(typed_alternative("--trust=full"), None);
+    }
+
+    #[test]
+    fn raw_arguments_with_a_typed_alternative_are_warned_about() {
+        let stdout = child_stdout("raw_arguments_child", &[("C3NE_TEST_CHILD", "1")]);
+        assert!(stdout.lines().any(|line| line == "cargo::warning=use optimization_level() instead of arg(\"-O3\")"));
+        assert!(
+            stdout
+                .lines()
+                .any(|line| line == "cargo::warning=use linking_mode() instead of subcommand(\"dynamic-lib\")")
+        );
+        assert!(!stdout.contains("--trust=full"));
+    }
+
+    #[test]
+    fn raw_arguments_child() {
+        if env::var_os("C3NE_TEST_CHILD").is_some() {
+            let mut build = C3FFI::new();
+            build.args(["-O3", "--trust=full"]).subcommand("dynamic-lib");
+            build.validate().unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn compile_bundle_builds_both_libraries() {
+        let dir = temp_dir("compile-bundle");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).file(&file);
+
+        let bundle = build.compile_bundle("thing").unwrap();
+        assert_eq!(bundle.static_library.library, dir.path().join("libthing.a"));
+        assert_eq!(bundle.dynamic_library.library, dir.path().join("libthing.so"));
+        assert!(bundle.static_library.library.is_file() && bundle.dynamic_library.library.is_file());
+        assert!(bundle.static_link_directives.contains(&"cargo::rustc-link-lib=static=thing".to_string()));
+        assert!(bundle.dynamic_link_directives.contains(&"cargo::rustc-link-lib=dylib=thing".to_string()));
+        assert!(build.link_directives.is_empty());
+    }
+
+    #[test]
+    fn threads_sets_the_backend_thread_count() {
+        let dir = temp_dir("threads");
+        let mut build = builder(&dir);
+        assert!(!args(&build).contains(&"--threads".to_string()));
+
+        build.threads(4);
+        assert!(contains_args(&args(&build), &["--threads", "4"]));
+        build.threads(0);
+        assert!(contains_args(&args(&build), &["--threads", "1"]));
+    }
+
+    #[test]
+    fn inherit_cargo_jobs_uses_num_jobs_unless_threads_is_set() {
+        let stdout = |num_jobs| {
+            child_stdout(
+                "inherit_cargo_jobs_child",
+                &[("C3NE_TEST_CHILD", "1"), ("NUM_JOBS", num_jobs)],
+            )
+        };
+        assert!(stdout("6").contains("inherited: --threads 6\n"));
+        assert!(stdout("6").contains("explicit: --threads 2\n"));
+        assert!(stdout("many").contains("inherited: \n"));
+    }
+
+    #[test]
+    fn inherit_cargo_jobs_child() {
+        if env::var_os("C3NE_TEST_CHILD").is_none() {
+            return;
+        }
+
+        let dir = temp_dir("inherit-cargo-jobs-child");
+        let mut build = builder(&dir);
+        build.inherit_cargo_jobs();
+        let threads = |build: &C3FFI| {
+            let args = args(build);
+            match args.iter().position(|arg| arg == "--threads") {
+                Some(index) => args[index..index + 2].join(" "),
+                None => String::new(),
+            }
+        };
+        println!("inherited: {}", threads(&build));
+        println!("explicit: {}", threads(build.threads(2)));
+    }
+
+    #[test]
+    fn optimization_level_for_overrides_the_level_of_matching_targets() {
+        let dir = temp_dir("optimization-level-for");
+        let mut build = builder(&dir);
+        build
+            .optimization_level(OptimizationLevel::O3)
+            .optimization_level_for("wasm32", OptimizationLevel::Oz)
+            .optimization_level_for("macos", OptimizationLevel::O1)
+            .optimization_level_for("aarch64", OptimizationLevel::O2)
+            .optimization_level_for("wasm32", OptimizationLevel::Os);
+
+        assert_eq!(args(&build)[2], "-O3");
+        build.c3_target("wasm32");
+        assert_eq!(args(&build)[2], "-Os");
+        build.c3_target("macos-aarch64");
+        assert_eq!(args(&build)[2], "-O1");
+        build.c3_target("linux-aarch64");
+        assert_eq!(args(&build)[2], "-O2");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn links_key_exports_metadata_for_the_declared_links_key() {
+        let unset = child_stdout("links_key_child", &[("C3NE_TEST_CHILD", "1")]);
+        assert!(unset.contains("cargo::warning=links_key(\"thing\") requires `links = \"thing\"` in Cargo.toml"));
+        assert!(!unset.contains("cargo::metadata="));
+
+        let declared = child_stdout("links_key_child", &[("C3NE_TEST_CHILD", "1"), ("CARGO_MANIFEST_LINKS", "thing")]);
+        assert!(declared.lines().any(|line| line.starts_with("cargo::metadata=include=")));
+        assert!(declared.lines().any(|line| line.starts_with("cargo::metadata=lib=") && line.ends_with("libthing.a")));
+
+        let mismatched =
+            child_stdout("links_key_child", &[("C3NE_TEST_CHILD", "1"), ("CARGO_MANIFEST_LINKS", "other")]);
+        assert!(mismatched.contains("links: Err(InvalidConfiguration("));
+        assert!(!mismatched.contains("cargo::metadata="));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn links_key_child() {
+        if env::var_os("C3NE_TEST_CHILD").is_none() {
+            return;
+        }
+
+        let dir = temp_dir("links-key-child");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).links_key("thing").file(&file);
+        println!("links: {:?}", build.attempt_compilation("thing").map(|_| ()));
+    }
+
+    #[test]
+    fn c3_target_skips_the_translation_of_target() {
+        let dir = temp_dir("c3-target");
+        let mut build = builder(&dir);
+        build.c3_target("freebsd-x64").override_arch("x86_64", "amd64");
+
+        assert!(contains_args(&args(&build), &["--target", "freebsd-x64"]));
+    }
+
+    #[test]
+    fn rust_target_to_c3_translates_common_triples() {
+        for (target, c3_target) in [
+            ("x86_64-unknown-linux-gnu", "linux-x64"),
+            ("i686-pc-windows-gnu", "mingw-x86"),
+            ("x86_64-pc-windows-msvc", "windows-x64"),
+            ("aarch64-apple-darwin", "macos-aarch64"),
+            ("aarch64-apple-ios", "ios-aarch64"),
+            ("aarch64-linux-android", "android-aarch64"),
+            ("riscv64gc-unknown-linux-gnu", "linux-riscv64"),
+            ("riscv32imac-unknown-none-elf", "elf-riscv32"),
+            ("x86_64-unknown-freebsd", "freebsd-x64"),
+            ("wasm32-unknown-unknown", "wasm32"),
+        ] {
+            assert_eq!(rust_target_to_c3(target).unwrap(), c3_target, "{}", target);
+        }
+
+        for target in ["armv7-unknown-linux-gnueabihf", "powerpc64le-unknown-linux-gnu", "xtensa-esp32-none-elf"] {
+            assert!(matches!(rust_target_to_c3(target), Err(C3Error::UnsupportedTarget(_))), "{}", target);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn check_libs_exist_reports_libraries_missing_from_the_search_dirs() {
+        let dir = temp_dir("check-libs-exist");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let lib_dir = dir.path().join("lib");
+        fs::create_dir_all(&lib_dir).unwrap();
+        fs::write(lib_dir.join("libfound.a"), "").unwrap();
+        fs::write(lib_dir.join("exact.o"), "").unwrap();
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, ""))
+            .check_libs_exist(true)
+            .compiled_lib_dir(&lib_dir)
+            .compiled_libs(["found", ":exact.o", "missing", ":libfound.so"])
+            .file(&file);
+
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::InvalidConfiguration(message))
+                if message == "compiled libraries not found in any compiled_lib_dir: missing, :libfound.so"
+        ));
+        assert!(!dir.path().join("c3c.args").exists());
+    }
+
+    #[test]
+    fn rust_target_to_c3_finds_the_os_in_any_segment() {
+        assert_eq!(rust_target_to_c3("x86_64-linux-gnu").unwrap(), "linux-x64");
+        assert_eq!(rust_target_to_c3("aarch64-unknown-none").unwrap(), "elf-aarch64");
+        assert_eq!(rust_target_to_c3("x86_64-linux-android").unwrap(), "android-x64");
+        assert_eq!(rust_target_to_c3("wasm32-unknown-unknown").unwrap(), "wasm32");
+        assert!(matches!(
+            rust_target_to_c3("x86_64-unknown-unknown"),
+            Err(C3Error::UnsupportedTarget(target)) if target == "x86_64-unknown-unknown"
+        ));
+        assert!(matches!(rust_target_to_c3("thumbv7em-none-eabihf"), Err(C3Error::UnsupportedTarget(_))));
+        assert!(matches!(rust_target_to_c3("x86_64"), Err(C3Error::UnsupportedTarget(_))));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn macos_sdk_is_passed_to_c3c_and_must_exist() {
+        let dir = temp_dir("macos-sdk");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let sdk = dir.path().join("MacOSX.sdk");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).c3_target("macos-aarch64").macos_sdk(&sdk).file(&file);
+        assert!(contains_args(&args(&build), &["--macossdk", sdk.to_str().unwrap()]));
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::InvalidConfiguration(message)) if message.starts_with("macOS SDK")
+        ));
+
+        fs::create_dir_all(&sdk).unwrap();
+        build.attempt_compilation("thing").unwrap();
+    }
+
+    #[test]
+    fn compiler_verbosity_selects_how_verbose_c3c_is() {
+        let dir = temp_dir("compiler-verbosity");
+        let mut build = builder(&dir);
+        for (level, flag) in [(1, "-v"), (2, "-vv"), (3, "-vvv"), (9, "-vvv")] {
+            build.compiler_verbosity(level);
+            assert!(args(&build).contains(&flag.to_string()), "{}", level);
+        }
+
+        build.verbose(true).compiler_verbosity(0);
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("-v")));
+    }
+
+    #[test]
+    fn windows_sdk_and_crt_only_apply_to_msvc_targets() {
+        let dir = temp_dir("windows-sdk");
+        let mut build = builder(&dir);
+        build.windows_sdk("C:/sdk").windows_crt(WinCrt::Static);
+        assert!(!args(&build).iter().any(|arg| arg == "--winsdk" || arg.starts_with("--wincrt")));
+
+        build.c3_target("windows-x64");
+        assert!(contains_args(&args(&build), &["--winsdk", "C:/sdk"]));
+        for (windows_crt, flag) in [
+            (WinCrt::None, "--wincrt=none"),
+            (WinCrt::Static, "--wincrt=static"),
+            (WinCrt::Dynamic, "--wincrt=dynamic"),
+        ] {
+            build.windows_crt(windows_crt);
+            assert!(args(&build).contains(&flag.to_string()));
+        }
+
+        build.c3_target("mingw-x64");
+        assert!(!args(&build).iter().any(|arg| arg == "--winsdk" || arg.starts_with("--wincrt")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn object_format_must_match_the_target() {
+        let dir = temp_dir("object-format");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).object_format(ObjectFormat::Elf).file(&file);
+        let elf_args = args(&build);
+        build.attempt_compilation("thing").unwrap();
+
+        build.object_format(ObjectFormat::MachO);
+        assert_eq!(args(&build), elf_args);
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::InvalidConfiguration(message))
+                if message == "object format MachO cannot be used with target linux-x64, which expects Elf"
+        ));
+    }
+
+    #[test]
+    fn reloc_model_selects_the_reloc_flag() {
+        let dir = temp_dir("reloc-model");
+        let mut build = builder(&dir);
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("--reloc")));
+
+        for (reloc_model, flag) in [
+            (RelocModel::Pic, "--reloc=pic"),
+            (RelocModel::Pie, "--reloc=pie"),
+            (RelocModel::Static, "--reloc=none"),
+        ] {
+            build.reloc_model(reloc_model);
+            assert!(args(&build).contains(&flag.to_string()));
+        }
+
+        build.reloc_model(RelocModel::Default);
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("--reloc")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn track_compiler_reruns_the_build_script_when_c3c_changes() {
+        let stdout = |track| child_stdout("track_compiler_child", &[("C3NE_TEST_TRACK", track)]);
+        let is_compiler_directive = |line: &str| {
+            line.starts_with("cargo::rerun-if-changed=")
+                && line.contains("c3ne-test-track-compiler-child-")
+                && line.ends_with("/c3c")
+        };
+        assert!(stdout("1").lines().any(is_compiler_directive));
+        assert!(!stdout("0").lines().any(is_compiler_directive));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn track_compiler_child() {
+        let Ok(track) = env::var("C3NE_TEST_TRACK") else {
+            return;
+        };
+
+        let dir = temp_dir("track-compiler-child");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).track_compiler(track == "1").file(&file);
+        build.attempt_compilation("thing").unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn emit_llvm_writes_the_ir_to_llvm_ir_dir() {
+        let dir = temp_dir("emit-llvm");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let llvm_ir_dir = dir.path().join("llvm").join("thing");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, &format!("mkdir -p '{0}' && touch '{0}/thing.ll'", llvm_ir_dir.display())))
+            .file(&file);
+        assert!(!args(&build).contains(&"--emit-llvm".to_string()));
+
+        build.emit_llvm(true);
+        assert_eq!(build.llvm_ir_dir("thing").unwrap(), llvm_ir_dir);
+        assert!(contains_args(&args(&build), &["--emit-llvm", "--llvm-out", llvm_ir_dir.to_str().unwrap()]));
+        build.attempt_compilation("thing").unwrap();
+        assert!(build.generated_files().contains(&llvm_ir_dir.join("thing.ll")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn emit_asm_writes_the_assembly_to_asm_dir() {
+        let dir = temp_dir("emit-asm");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let asm_dir = dir.path().join("asm").join("thing");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, &format!("mkdir -p '{0}' && touch '{0}/thing.s'", asm_dir.display())))
+            .file(&file);
+        assert!(!args(&build).contains(&"--emit-asm".to_string()));
+
+        build.emit_asm(true);
+        assert_eq!(build.asm_dir("thing").unwrap(), asm_dir);
+        assert!(contains_args(&args(&build), &["--emit-asm", "--asm-out", asm_dir.to_str().unwrap()]));
+        build.attempt_compilation("thing").unwrap();
+        assert!(build.generated_files().contains(&asm_dir.join("thing.s")));
+    }
+
+    #[test]
+    fn error_format_renders_failed_compilations() {
+        let failed = |format| C3Error::CompilationFailed {
+            code: Some(1),
+            stderr: "(src/thing.c3:3:5) Error: unknown type\nWarning: unused \"x\"\n".to_string(),
+            format,
+        };
+
+        assert_eq!(
+            failed(ErrorFormat::GithubActions).to_string(),
+            "::error file=src/thing.c3,line=3,col=5::unknown type\n::warning::unused \"x\"\n"
+        );
+        assert_eq!(
+            failed(ErrorFormat::Json).to_string(),
+            concat!(
+                r#"{"code": 1, "diagnostics": ["#,
+                r#"{"severity": "error", "file": "src/thing.c3", "line": 3, "column": 5, "message": "unknown type"}, "#,
+                r#"{"severity": "warning", "file": null, "line": null, "column": null, "message": "unused \"x\""}"#,
+                r#"], "stderr": "(src/thing.c3:3:5) Error: unknown type\nWarning: unused \"x\"\n"}"#,
+            )
+        );
+
+        let crashed = C3Error::CompilationFailed {
+            code: None,
+            stderr: "Warning: unused\n".to_string(),
+            format: ErrorFormat::GithubActions,
+        };
+        assert_eq!(crashed.to_string(), "::warning::unused\n::error::c3c was terminated by a signal");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn check_runs_c3c_without_building_a_library() {
+        let dir = temp_dir("check");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, r#"mkdir -p "$obj_dir" && touch "$obj_dir/thing.o""#))
+            .file(&file);
+        fs::create_dir_all(dir.path().join("obj").join("check")).unwrap();
+        fs::write(dir.path().join("libcheck.a"), "").unwrap();
+
+        build.check().unwrap();
+        assert!(!build.check_only);
+        assert_eq!(recorded_args(&dir)[0][0], "compile-only");
+        assert!(build.link_directives.is_empty());
+        assert!(!dir.path().join("libthing.a").exists() && !dir.path().join("lib__c3ne_check.a").exists());
+        // A library that happens to be called `check` is left alone, and nothing of the check itself remains.
+        assert!(dir.path().join("libcheck.a").is_file() && dir.path().join("obj").join("check").is_dir());
+        assert!(!dir.path().join("obj").join("__c3ne_check").exists());
+
+        fake_c3c(&dir, "exit 1");
+        assert!(matches!(build.check(), Err(C3Error::CompilationFailed { .. })));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn compile_to_temp_builds_into_a_directory_removed_on_drop() {
+        let dir = temp_dir("compile-to-temp");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = C3FFI::new();
+        build.compiler(&fake_c3c(&dir, "")).c3_target("linux-x64").file(&file);
+
+        let (temp, library) = build.compile_to_temp("thing").unwrap();
+        assert_eq!(library, temp.path().join("libthing.a"));
+        assert!(library.is_file());
+        assert!(build.out_dir.is_none() && !build.defer_link_directives);
+
+        let temp_path = temp.path().to_path_buf();
+        drop(temp);
+        assert!(!temp_path.exists());
+    }
+
+    #[test]
+    fn command_line_starts_with_the_compiler_and_ends_with_the_sources() {
+        let dir = temp_dir("command-line");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler("/opt/c3/c3c").file(&file).arg("--trust=full");
+
+        let command_line = build.command_line("thing").unwrap();
+        assert_eq!(command_line[..2], ["/opt/c3/c3c", "static-lib"]);
+        let output_dir = command_line.iter().position(|arg| arg == "--output-dir").unwrap();
+        assert_eq!(command_line[output_dir + 1], dir.path().join("staging").join("thing").display().to_string());
+        assert_eq!(command_line[command_line.len() - 2..], [file.display().to_string(), "--trust=full".to_string()]);
+        assert!(!dir.path().join("staging").exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn intermediates_dir_is_created_and_passed_as_the_build_dir() {
+        let dir = temp_dir("intermediates-dir");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).intermediates_dir("c3-intermediates").file(&file);
+
+        let intermediates_dir = dir.path().join("c3-intermediates");
+        assert!(contains_args(&args(&build), &["--build-dir", intermediates_dir.to_str().unwrap()]));
+        build.attempt_compilation("thing").unwrap();
+        assert!(intermediates_dir.is_dir());
+
+        let absolute = dir.path().join("elsewhere");
+        build.intermediates_dir(&absolute);
+        assert!(contains_args(&args(&build), &["--build-dir", absolute.to_str().unwrap()]));
+    }
+
+    #[test]
+    fn conflicting_features_cannot_be_enabled_together() {
+        let mut build = C3FFI::new();
+        build.conflicting_features("FAST", "SMALL").feature("FAST");
+        assert!(build.validate().is_ok());
+
+        build.feature("SMALL");
+        assert!(matches!(
+            build.validate(),
+            Err(C3Error::ConflictingFeatures(a, b)) if a == "FAST" && b == "SMALL"
+        ));
+    }
+
+    #[test]
+    fn missing_source_files_are_reported_before_running_c3c() {
+        let dir = temp_dir("missing-source");
+        let present = source(&dir, "present.c3", "module present;");
+        let missing = dir.path().join("missing.c3");
+        let mut build = builder(&dir);
+        build.compiler("c3ne-test-nonexistent-c3c").files([&present, &missing]);
+
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::MissingSourceFile(file)) if file == missing
+        ));
+    }
+
+    #[test]
+    fn a_missing_compiler_is_reported_by_name() {
+        let dir = temp_dir("compiler-not-found");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler("c3ne-test-nonexistent-c3c").file(&file);
+
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::CompilerNotFound(compiler)) if compiler == "c3ne-test-nonexistent-c3c"
+        ));
+    }
+
+    #[test]
+    fn versions_are_parsed_leniently() {
+        assert_eq!(parse_version("0.7.6"), Some((0, 7, 6)));
+        assert_eq!(parse_version("0.7"), Some((0, 7, 0)));
+        assert_eq!(parse_version("1"), Some((1, 0, 0)));
+        assert_eq!(parse_version("0.6.8 (Pre-release)"), Some((0, 6, 8)));
+        assert_eq!(parse_version("0.7.2-dev"), Some((0, 7, 2)));
+        assert_eq!(parse_version("v0.7.6"), None);
+        assert_eq!(parse_version(""), None);
+
+        let version_info = "C3 Compiler Version:       0.7.6\nInstalled directory:       /opt/c3\n";
+        assert_eq!(version_field(version_info, "C3 Compiler"), Some("0.7.6"));
+        assert_eq!(version_field(version_info, "Stdlib"), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn require_min_version_rejects_older_compilers() {
+        let dir = temp_dir("min-version");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(
+                &dir,
+                r#"[ "$command" = --version ] && echo 'C3 Compiler Version: 0.7.1 (Pre-release)' && exit 0"#,
+            ))
+            .file(&file);
+
+        build.require_min_version("0.7");
+        assert!(build.attempt_compilation("thing").is_ok());
+        build.require_min_version("0.7.1");
+        assert!(build.attempt_compilation("thing").is_ok());
+        build.require_min_version("0.7.2");
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::CompilerTooOld { found, required }) if found == "0.7.1 (Pre-release)" && required == "0.7.2"
+        ));
+
+        build.require_min_version("not a version");
+        assert_eq!(build.required_min_version, Some((0, 7, 2)));
+    }
+
+    #[test]
+    fn available_targets_skip_headings_and_blank_lines() {
+        let target_list = "Available targets:\n   linux-x64\n   macos-aarch64\n\n   windows-x64\n";
+        assert_eq!(available_targets(target_list), ["linux-x64", "macos-aarch64", "windows-x64"]);
+        assert!(available_targets("").is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn verify_target_checks_the_targets_c3c_lists() {
+        let dir = temp_dir("verify-target");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(
+                &dir,
+                r#"[ "$command" = --list-targets ] && printf 'Available targets:\n  linux-x64\n  wasm32\n' && exit 0"#,
+            ))
+            .verify_target(true)
+            .file(&file);
+        assert!(build.attempt_compilation("thing").is_ok());
+
+        build.c3_target("macos-aarch64");
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::TargetNotInstalled { target, available })
+                if target == "macos-aarch64" && available == ["linux-x64", "wasm32"]
+        ));
+    }
+
+    #[test]
+    fn library_paths_rerun_the_build_script_once_each() {
+        let stdout = child_stdout("library_paths_child", &[("C3NE_TEST_CHILD", "1")]);
+        let reruns: Vec<_> = stdout.lines().filter_map(|line| line.strip_prefix("cargo::rerun-if-changed=")).collect();
+        assert_eq!(reruns, ["vendor", "patched", "prebuilt", "Cargo.toml"]);
+    }
+
+    #[test]
+    fn library_paths_child() {
+        if env::var_os("C3NE_TEST_CHILD").is_none() {
+            return;
+        }
+
+        C3FFI::new()
+            .c3_lib_dir("vendor")
+            .c3_lib_dirs(["vendor", "patched"])
+            .prepend_c3_lib_dir("patched")
+            .compiled_lib_dir("prebuilt")
+            .prepend_compiled_lib_dir("prebuilt")
+            .c3_lib("somelib")
+            .c3_lib("Cargo.toml")
+            .c3_lib("Cargo.toml");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn merge_with_combines_prebuilt_archives_into_the_library() {
+        let dir = temp_dir("merge-with");
+        let archive = |name: &str, code: &str| {
+            let object = dir.path().join(format!("{}.o", name));
+            let library = dir.path().join(format!("lib{}.a", name));
+            let status = Command::new("cc")
+                .args(["-x", "c", "-c", "-fPIC", "-o"])
+                .arg(&object)
+                .arg(source(&dir, &format!("{}.c", name), code))
+                .status()
+                .unwrap();
+            assert!(status.success());
+            assert!(Command::new("ar").arg("rcs").arg(&library).arg(&object).status().unwrap().success());
+            library
+        };
+        let c3_side = archive("c3side", "int from_c3(void) { return 1; }");
+        let helpers = archive("helpers", "int from_helpers(void) { return 2; }");
+
+        let file = source(&dir, "thing.c3", "module thing;");
+        let script = format!(
+            r#"[ "$command" = static-lib ] && mkdir -p "$out_dir" && cp '{}' "$out_dir/$out_name.a""#,
+            c3_side.display()
+        );
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, &script)).merge_with(&[helpers]).file(&file);
+        let result = build.attempt_compilation("thing").unwrap();
+
+        let symbols = Command::new("nm").arg(&result.library).output().unwrap();
+        let symbols = String::from_utf8(symbols.stdout).unwrap();
+        assert!(symbols.contains("T from_c3"));
+        assert!(symbols.contains("T from_helpers"));
+    }
+
+    #[test]
+    fn environment_variables_rerun_the_build_script_once_each() {
+        let stdout = child_stdout("environment_variables_child", &[("C3NE_TEST_CHILD", "1")]);
+        let reruns: Vec<_> =
+            stdout.lines().filter_map(|line| line.strip_prefix("cargo::rerun-if-env-changed=")).collect();
+        assert_eq!(reruns, ["FOO", "BAR", "LD_LIBRARY_PATH"]);
+    }
+
+    #[test]
+    fn environment_variables_child() {
+        if env::var_os("C3NE_TEST_CHILD").is_none() {
+            return;
+        }
+
+        C3FFI::new()
+            .environment_variable(("FOO", "on"))
+            .environment_variable(("FOO", "off"))
+            .environment_variables([("BAR", "1"), ("FOO", "on")])
+            .phase_env(Phase::VerifyLink, "LD_LIBRARY_PATH", "extern/lib")
+            .phase_env(Phase::VerifyLink, "LD_LIBRARY_PATH", "extern/lib64");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn cache_skips_c3c_until_an_input_changes() {
+        let dir = temp_dir("cache");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).cache(true).file(&file);
+
+        let first = build.attempt_compilation("thing").unwrap();
+        let link_directives = build.link_directives.clone();
+        assert!(dir.path().join("thing.c3ne-cache").is_file());
+        let second = build.attempt_compilation("thing").unwrap();
+        assert_eq!(second.library, first.library);
+        assert_eq!(build.link_directives, link_directives);
+        assert_eq!(recorded_args(&dir).len(), 1);
+
+        fs::write(&file, "module thing;\nfn void f() {}").unwrap();
+        build.attempt_compilation("thing").unwrap();
+        build.feature("FAST").attempt_compilation("thing").unwrap();
+        assert_eq!(recorded_args(&dir).len(), 3);
+
+        fs::remove_file(&first.library).unwrap();
+        build.attempt_compilation("thing").unwrap();
+        assert_eq!(recorded_args(&dir).len(), 4);
+
+        build.cache(false).attempt_compilation("thing").unwrap();
+        assert_eq!(recorded_args(&dir).len(), 5);
+    }
+
+    #[test]
+    fn files_from_list_resolves_paths_against_the_listing() {
+        let dir = temp_dir("files-from-list");
+        let list = source(
+            &dir,
+            "sources.txt",
+            "# generated by hand\nthing.c3\n\n  nested/other.c3  \n/abs/lib.c3\nthing.c3\n",
+        );
+        let mut build = C3FFI::new();
+        build.files_from_list(&list).unwrap();
+        assert_eq!(
+            build.files,
+            [dir.path().join("thing.c3"), dir.path().join("nested/other.c3"), PathBuf::from("/abs/lib.c3")]
+        );
+
+        assert!(matches!(
+            C3FFI::new().files_from_list(dir.path().join("missing.txt")),
+            Err(C3Error::Io(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn compile_many_builds_every_library_and_collects_the_failures() {
+        let dir = temp_dir("compile-many-failures");
+        let shared = source(&dir, "shared.c3", "module shared;");
+        let files: Vec<_> = ["good", "bad", "worse"]
+            .iter()
+            .map(|name| vec![source(&dir, &format!("{}.c3", name), &format!("module {};", name))])
+            .collect();
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, r#"case $out_name in *bad|*worse) echo "Error: $out_name" >&2; exit 1 ;; esac"#))
+            .file(&shared);
+
+        let Err(C3Error::Multiple(errors)) =
+            build.compile_many(&[("good", &files[0]), ("bad", &files[1]), ("worse", &files[2])])
+        else {
+            panic!("expected several failures");
+        };
+        let names: Vec<_> = errors.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["bad", "worse"]);
+        assert!(errors.iter().all(|(_, err)| matches!(err, C3Error::CompilationFailed { .. })));
+        assert!(dir.path().join("libgood.a").is_file());
+        assert_eq!(build.files, [shared]);
+
+        assert!(matches!(
+            build.compile_many(&[("good", &files[0]), ("bad", &files[1])]),
+            Err(C3Error::CompilationFailed { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn compile_many_puts_generated_sources_into_the_first_library() {
+        let dir = temp_dir("compile-many-generated");
+        let first = [source(&dir, "first.c3", "module first;")];
+        let second = [source(&dir, "second.c3", "module second;")];
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, ""))
+            .max_parallel(1)
+            .source_bytes("generated.c3", b"module generated;");
+
+        build.compile_many(&[("first", &first), ("second", &second)]).unwrap();
+        let runs = recorded_args(&dir);
+        assert_eq!(runs.len(), 2);
+        assert!(runs[0].iter().any(|arg| arg.ends_with("generated.c3")));
+        assert!(!runs[1].iter().any(|arg| arg.ends_with("generated.c3")));
+        assert_eq!(build.generated_sources.len(), 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn compile_many_skips_failed_libraries_when_optional() {
+        let dir = temp_dir("compile-many-optional");
+        let good = [source(&dir, "good.c3", "module good;")];
+        let bad = [source(&dir, "bad.c3", "module bad;")];
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, r#"case $out_name in *bad) echo "Error: $out_name" >&2; exit 1 ;; esac"#))
+            .optional(true);
+
+        build.compile_many(&[("good", &good), ("bad", &bad)]).unwrap();
+        assert!(dir.path().join("libgood.a").is_file());
+        assert!(!dir.path().join("libbad.a").exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn sanitizer_instruments_the_library_and_links_its_runtime() {
+        let dir = temp_dir("sanitizer");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).file(&file);
+        assert!(!args(&build).iter().any(|arg| arg.starts_with("--sanitize")));
+        build.attempt_compilation("thing").unwrap();
+        assert!(!build.link_directives.iter().any(|directive| directive.contains("-fsanitize")));
+
+        for (sanitizer, flag) in [
+            (Sanitizer::Address, "--sanitize=address"),
+            (Sanitizer::Thread, "--sanitize=thread"),
+            (Sanitizer::Memory, "--sanitize=memory"),
+        ] {
+            build.sanitizer(sanitizer);
+            assert!(args(&build).contains(&flag.to_string()));
+            build.attempt_compilation("thing").unwrap();
+            let link_arg = format!("cargo::rustc-link-arg=-fsanitize={}", sanitizer.to_str());
+            assert!(build.link_directives.contains(&link_arg));
+        }
+    }
+
+    #[test]
+    fn diagnostics_are_parsed_into_json() {
+        let json = |line| Diagnostic::parse(line).map(|diagnostic| diagnostic.to_json());
+        assert_eq!(
+            json("(src/thing.c3:3:5) Error: expected ';'").unwrap(),
+            r#"{"severity": "error", "file": "src/thing.c3", "line": 3, "column": 5, "message": "expected ';'"}"#
+        );
+        assert_eq!(
+            json(r#"  (C:\src\thing.c3:12:1) Warning: unused "x""#).unwrap(),
+            concat!(
+                r#"{"severity": "warning", "file": "C:\\src\\thing.c3", "line": 12, "column": 1, "#,
+                r#""message": "unused \"x\""}"#
+            )
+        );
+        assert_eq!(
+            json("Error: no files to compile").unwrap(),
+            r#"{"severity": "error", "file": null, "line": null, "column": null, "message": "no files to compile"}"#
+        );
+        assert!(json("Program linked to static library.").is_none());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn diagnostics_json_is_written_whether_or_not_the_build_fails() {
+        let dir = temp_dir("diagnostics-json");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let diagnostics_json = dir.path().join("diagnostics.json");
+        let mut build = builder(&dir);
+        build
+            .compiler(&fake_c3c(&dir, "echo '(thing.c3:1:1) Warning: unused import' >&2"))
+            .diagnostics_json(&diagnostics_json)
+            .file(&file);
+        build.attempt_compilation("thing").unwrap();
+        assert_eq!(
+            fs::read_to_string(&diagnostics_json).unwrap(),
+            concat!(
+                r#"[{"severity": "warning", "file": "thing.c3", "line": 1, "column": 1, "message": "unused import"}]"#,
+                "\n"
+            )
+        );
+
+        fake_c3c(&dir, "printf 'compiling\\n(thing.c3:2:3) Error: boom\\nError: giving up\\n' >&2; exit 1");
+        assert!(build.attempt_compilation("thing").is_err());
+        assert_eq!(
+            fs::read_to_string(&diagnostics_json).unwrap(),
+            concat!(
+                r#"[{"severity": "error", "file": "thing.c3", "line": 2, "column": 3, "message": "boom"}, "#,
+                r#"{"severity": "error", "file": null, "line": null, "column": null, "message": "giving up"}]"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn subcommand_for_overrides_a_single_linking_mode() {
+        let dir = temp_dir("subcommand-for");
+        let mut build = builder(&dir);
+        build.subcommand_for(LinkingMode::Static, "static-library");
+        assert_eq!(args(&build)[0], "static-library");
+        build.linking_mode(LinkingMode::Dynamic);
+        assert_eq!(args(&build)[0], "dynamic-lib");
+
+        build.subcommand_for(LinkingMode::Dynamic, "shared-lib").subcommand_for(LinkingMode::Dynamic, "shared-library");
+        assert_eq!(args(&build)[0], "shared-library");
+        assert_eq!(build.subcommand_overrides.len(), 2);
+
+        build.subcommand("build");
+        assert_eq!(args(&build)[0], "build");
+    }
+
+    #[test]
+    fn panic_messages_are_only_forwarded_when_set() {
+        let dir = temp_dir("panic-messages");
+        let mut build = builder(&dir);
+        build.optimization_level(OptimizationLevel::O2);
+        let panic_args = |build: &C3FFI| -> Vec<String> {
+            args(build)
+                .into_iter()
+                .filter(|arg| arg.starts_with("--panic-msg") || arg.starts_with("--show-backtrace"))
+                .collect()
+        };
+        assert!(panic_args(&build).is_empty());
+
+        build.panic_messages(true).backtrace(true);
+        assert_eq!(panic_args(&build), ["--panic-msg=yes", "--show-backtrace=yes"]);
+        build.panic_messages(false).backtrace(false);
+        assert_eq!(panic_args(&build), ["--panic-msg=no", "--show-backtrace=no"]);
+
+        build.panic_strategy(PanicStrategy::Unwind);
+        assert_eq!(panic_args(&build), ["--panic-msg=no", "--show-backtrace=no"]);
+    }
+
+    #[test]
+    fn backtrace_is_independent_of_panic_messages() {
+        let dir = temp_dir("backtrace");
+        let mut build = builder(&dir);
+        build.panic_strategy(PanicStrategy::Unwind).backtrace(false);
+
+        let args = args(&build);
+        assert!(args.contains(&"--panic-msg=yes".to_string()));
+        assert!(args.contains(&"--show-backtrace=no".to_string()));
+    }
+
+    #[test]
+    fn object_format_of_target_follows_the_os() {
+        assert_eq!(ObjectFormat::of_target("linux-x64"), ObjectFormat::Elf);
+        assert_eq!(ObjectFormat::of_target("elf-riscv32"), ObjectFormat::Elf);
+        assert_eq!(ObjectFormat::of_target("macos-aarch64"), ObjectFormat::MachO);
+        assert_eq!(ObjectFormat::of_target("windows-x64"), ObjectFormat::Coff);
+        assert_eq!(ObjectFormat::of_target("mingw-x86"), ObjectFormat::Coff);
+        assert_eq!(ObjectFormat::of_target("wasm32"), ObjectFormat::Wasm);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn max_parallel_limits_how_many_libraries_are_built_at_once() {
+        let dir = temp_dir("max-parallel");
+        let lock = dir.path().join("lock");
+        let overlap = dir.path().join("overlap");
+        let script = format!(
+            "mkdir '{lock}' || touch '{overlap}'\nsleep 0.1\nrmdir '{lock}'",
+            lock = lock.display(),
+            overlap = overlap.display()
+        );
+        let files: Vec<Vec<PathBuf>> = ["a", "b", "c"]
+            .iter()
+            .map(|name| vec![source(&dir, &format!("{}.c3", name), &format!("module {};", name))])
+            .collect();
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, &script)).max_parallel(1);
+
+        build
+            .compile_many(&[("a", &files[0]), ("b", &files[1]), ("c", &files[2])])
+            .unwrap();
+        for name in ["a", "b", "c"] {
+            assert!(dir.path().join(format!("lib{}.a", name)).is_file());
+        }
+        assert!(!overlap.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn modify_args_changes_every_c3c_invocation() {
+        let dir = temp_dir("modify-args");
+        let a = [source(&dir, "a.c3", "module a;")];
+        let b = [source(&dir, "b.c3", "module b;")];
+        let mut build = builder(&dir);
+        let mut calls = 0;
+        // One library at a time, so the stand-in's recorded arguments don't interleave.
+        build.compiler(&fake_c3c(&dir, "")).max_parallel(1).modify_args(move |args| {
+            calls += 1;
+            args.push(format!("--call={}", calls));
+        });
+
+        build.compile_many(&[("a", &a), ("b", &b)]).unwrap();
+        build.file(&a[0]).attempt_compilation("thing").unwrap();
+
+        let runs = recorded_args(&dir);
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].last().unwrap(), "--call=1");
+        assert_eq!(runs[1].last().unwrap(), "--call=2");
+        assert_eq!(runs[2].last().unwrap(), "--call=3");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn dump_command_to_writes_every_invocation_to_a_script() {
+        let dir = temp_dir("dump-command");
+        let a = [source(&dir, "a.c3", "module a;")];
+        let b = [source(&dir, "b.c3", "module b;")];
+        let script = dir.path().join("c3c.sh");
+        let compiler = fake_c3c(&dir, "");
+        let mut build = builder(&dir);
+        build
+            .compiler(&compiler)
+            .max_parallel(1)
+            .environment_variable(("TOKEN", "secret value"))
+            .dump_command_to(&script);
+        build.compile_many(&[("a", &a), ("b", &b)]).unwrap();
+
+        let dumped = fs::read_to_string(&script).unwrap();
+        let commands: Vec<&str> = dumped.lines().filter(|line| !line.starts_with('#') && *line != "set -e").collect();
+        assert_eq!(commands.len(), 2);
+        for (command, file) in commands.iter().zip([&a[0], &b[0]]) {
+            assert!(command.starts_with(&format!("TOKEN='secret value' {} static-lib ", compiler)));
+            assert!(command.ends_with(&format!(" {}", file.display())));
+        }
+        assert_eq!(build.generated_files().iter().filter(|file| **file == script).count(), 1);
+
+        build.mask_dumped_environment(true).file(&a[0]).attempt_compilation("thing").unwrap();
+        let dumped = fs::read_to_string(&script).unwrap();
+        assert!(dumped.contains("TOKEN='***' "));
+        assert!(!dumped.contains("secret"));
+    }
+
+    #[test]
+    fn only_if_feature_skips_a_disabled_build_before_validating_it() {
+        let dir = temp_dir("only-if-feature");
+        let missing = dir.path().join("missing.c3");
+        let mut build = builder(&dir);
+        build.compiler("c3ne-test-no-such-compiler").only_if_feature("c3ne-test-disabled");
+
+        build.file(&missing);
+        assert!(build.attempt_compilation("thing").unwrap().skipped);
+        build.files.clear();
+        assert!(build.compile_many(&[("thing", &[missing])]).is_ok());
+    }
+
+    #[test]
+    fn case_insensitive_env_merges_names_differing_in_case() {
+        assert!(same_env_key("PATH", "PATH", false));
+        assert!(!same_env_key("PATH", "Path", false));
+        assert!(same_env_key("PATH", "Path", true));
+        assert!(!same_env_key("PATH", "PATHEXT", true));
+
+        let mut build = C3FFI::new();
+        build
+            .case_insensitive_env(true)
+            .environment_variable(("PATH", "C:\\c3"))
+            .environment_variable(("Path", "C:\\c3\\bin"))
+            .phase_env(Phase::Compile, "TEMP", "C:\\tmp")
+            .phase_env(Phase::Compile, "Temp", "D:\\tmp");
+        let environment = |build: &C3FFI| -> Vec<(String, String)> {
+            let environment = build.environment_for(Phase::Compile);
+            environment.into_iter().map(|(key, value)| (key.clone(), value.clone())).collect()
+        };
+        assert_eq!(
+            environment(&build),
+            [
+                ("Path".to_string(), "C:\\c3\\bin".to_string()),
+                ("Temp".to_string(), "D:\\tmp".to_string())
+            ]
+        );
+
+        let mut build = C3FFI::new();
+        build
+            .case_insensitive_env(false)
+            .environment_variable(("PATH", "/c3"))
+            .environment_variable(("Path", "/c3/bin"));
+        assert_eq!(environment(&build).len(), 2);
+
+        // Names are only folded when the command is built, so the toggle can come after the variables.
+        build.case_insensitive_env(true);
+        assert_eq!(environment(&build), [("Path".to_string(), "/c3/bin".to_string())]);
+    }
+
+    #[test]
+    fn case_insensitive_env_defaults_to_the_target_os() {
+        let count = |target_os| {
+            let envs = [("C3NE_TEST_CHILD", "1"), ("CARGO_CFG_TARGET_OS", target_os)];
+            let stdout = child_stdout("case_insensitive_env_child", &envs);
+            stdout.lines().find_map(|line| line.strip_prefix("variables: ")).unwrap().to_string()
+        };
+        assert_eq!(count("windows"), "1");
+        assert_eq!(count("linux"), "2");
+    }
+
+    #[test]
+    fn case_insensitive_env_child() {
+        if env::var_os("C3NE_TEST_CHILD").is_none() {
+            return;
+        }
+
+        let mut build = C3FFI::new();
+        build.environment_variable(("PATH", "a")).environment_variable(("Path", "b"));
+        println!("variables: {}", build.environment_for(Phase::Compile).len());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn keep_symbols_cannot_be_used_with_static_libraries() {
+        let dir = temp_dir("keep-symbols-static");
+        let file = source(&dir, "thing.c3", "module thing;");
+        let mut build = builder(&dir);
+        build.compiler(&fake_c3c(&dir, "")).keep_symbols(&["entry"]).file(&file);
+
+        assert!(matches!(
+            build.attempt_compilation("thing"),
+            Err(C3Error::InvalidConfiguration(_))
+        ));
+        assert!(!args(&build).iter().any(|arg| arg.contains("entry")));
+    }
+}